@@ -0,0 +1,94 @@
+-- Parsed sectional data, one row per race keyed by race_code. Child rows
+-- are replaced whenever a race is loaded again.
+
+CREATE TABLE meetings (
+    meeting_code INTEGER PRIMARY KEY,
+    event_date DATE NOT NULL,
+    event_name TEXT NOT NULL,
+    course_name TEXT NOT NULL,
+    track_name TEXT NOT NULL
+);
+
+CREATE TABLE races (
+    race_code INTEGER PRIMARY KEY,
+    meeting_code INTEGER NOT NULL REFERENCES meetings (meeting_code),
+    race_number INTEGER NOT NULL,
+    race_name TEXT NOT NULL,
+    distance INTEGER,
+    finish_time INTERVAL NOT NULL,
+    track_condition TEXT NOT NULL,
+    rail_position TEXT NOT NULL
+);
+
+CREATE INDEX races_meeting_code ON races (meeting_code);
+
+CREATE TABLE runners (
+    race_code INTEGER NOT NULL REFERENCES races (race_code) ON DELETE CASCADE,
+    horse_code INTEGER NOT NULL,
+    horse_name TEXT NOT NULL,
+    bib INTEGER NOT NULL,
+    draw_number INTEGER NOT NULL,
+    distance_travelled INTEGER NOT NULL,
+    distance_difference INTEGER NOT NULL,
+    final_rank SMALLINT NOT NULL,
+    time_official BOOLEAN NOT NULL,
+    official_margin DOUBLE PRECISION NOT NULL,
+    fastest_section_time INTERVAL NOT NULL,
+    fastest_section_index SMALLINT NOT NULL,
+    top_speed DOUBLE PRECISION NOT NULL,
+    top_speed_index SMALLINT NOT NULL,
+    finish_time INTERVAL NOT NULL,
+    result_state TEXT NOT NULL,
+    result_substate TEXT NOT NULL,
+    PRIMARY KEY (race_code, horse_code)
+);
+
+CREATE INDEX runners_horse_code ON runners (horse_code);
+
+CREATE TABLE sections (
+    race_code INTEGER NOT NULL,
+    horse_code INTEGER NOT NULL,
+    section_number INTEGER NOT NULL,
+    cumulated_distance INTEGER NOT NULL,
+    margin_decimal DOUBLE PRECISION NOT NULL,
+    real_distance DOUBLE PRECISION NOT NULL,
+    rank INTEGER NOT NULL,
+    intermediate_time INTERVAL NOT NULL,
+    section_time INTERVAL NOT NULL,
+    avg_speed DOUBLE PRECISION NOT NULL,
+    top_speed DOUBLE PRECISION NOT NULL,
+    avg_stride_freq DOUBLE PRECISION NOT NULL,
+    average_stride_length DOUBLE PRECISION NOT NULL,
+    avg_distance_rail DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (race_code, horse_code, section_number),
+    FOREIGN KEY (race_code, horse_code) REFERENCES runners ON DELETE CASCADE
+);
+
+CREATE TABLE speeds (
+    race_code INTEGER NOT NULL,
+    horse_code INTEGER NOT NULL,
+    point INTEGER NOT NULL,
+    distance INTEGER NOT NULL,
+    speed DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (race_code, horse_code, point),
+    FOREIGN KEY (race_code, horse_code) REFERENCES runners ON DELETE CASCADE
+);
+
+CREATE TABLE ranks (
+    race_code INTEGER NOT NULL,
+    horse_code INTEGER NOT NULL,
+    point INTEGER NOT NULL,
+    distance INTEGER NOT NULL,
+    rank INTEGER NOT NULL,
+    PRIMARY KEY (race_code, horse_code, point),
+    FOREIGN KEY (race_code, horse_code) REFERENCES runners ON DELETE CASCADE
+);
+
+CREATE TABLE fastest_sections (
+    race_code INTEGER NOT NULL REFERENCES races (race_code) ON DELETE CASCADE,
+    section_number INTEGER NOT NULL,
+    cumulated_distance INTEGER NOT NULL,
+    intermediate_time INTERVAL NOT NULL,
+    section_time INTERVAL NOT NULL,
+    PRIMARY KEY (race_code, section_number)
+);
@@ -0,0 +1,9 @@
+-- The leader's early and late splits and the pace they make, rated against
+-- the benchmarks of the races loaded with it.
+
+ALTER TABLE races
+    ADD COLUMN early_split INTERVAL,
+    ADD COLUMN late_split INTERVAL,
+    ADD COLUMN pace TEXT,
+    ADD COLUMN early_pace_index DOUBLE PRECISION,
+    ADD COLUMN late_pace_index DOUBLE PRECISION;
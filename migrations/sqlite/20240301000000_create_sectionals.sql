@@ -0,0 +1,96 @@
+-- Parsed sectional data, one row per race keyed by race_code. Child rows
+-- are replaced whenever a race is written again. Times are in seconds.
+
+CREATE TABLE races (
+    race_code INTEGER PRIMARY KEY,
+    event_date TEXT NOT NULL,
+    meeting_code INTEGER NOT NULL,
+    race_number INTEGER NOT NULL,
+    event_name TEXT NOT NULL,
+    course_name TEXT NOT NULL,
+    race_name TEXT NOT NULL,
+    distance INTEGER,
+    finish_time REAL NOT NULL,
+    track_name TEXT NOT NULL,
+    track_condition TEXT NOT NULL,
+    rail_position TEXT NOT NULL
+);
+
+CREATE INDEX races_event_date ON races (event_date);
+CREATE INDEX races_course_name ON races (course_name);
+
+CREATE TABLE runners (
+    race_code INTEGER NOT NULL REFERENCES races (race_code) ON DELETE CASCADE,
+    horse_code INTEGER NOT NULL,
+    horse_name TEXT NOT NULL,
+    bib INTEGER NOT NULL,
+    draw_number INTEGER NOT NULL,
+    distance_travelled INTEGER NOT NULL,
+    distance_difference INTEGER NOT NULL,
+    final_rank INTEGER NOT NULL,
+    time_official INTEGER NOT NULL,
+    official_margin REAL NOT NULL,
+    fastest_section_time REAL NOT NULL,
+    fastest_section_index INTEGER NOT NULL,
+    top_speed REAL NOT NULL,
+    top_speed_index INTEGER NOT NULL,
+    finish_time REAL NOT NULL,
+    result_state TEXT NOT NULL,
+    result_substate TEXT NOT NULL,
+    PRIMARY KEY (race_code, horse_code)
+);
+
+CREATE INDEX runners_horse_code ON runners (horse_code);
+
+CREATE TABLE sections (
+    race_code INTEGER NOT NULL,
+    horse_code INTEGER NOT NULL,
+    section_number INTEGER NOT NULL,
+    cumulated_distance INTEGER NOT NULL,
+    margin_decimal REAL NOT NULL,
+    real_distance REAL NOT NULL,
+    rank INTEGER NOT NULL,
+    intermediate_time REAL NOT NULL,
+    section_time REAL NOT NULL,
+    avg_speed REAL NOT NULL,
+    top_speed REAL NOT NULL,
+    avg_stride_freq REAL NOT NULL,
+    average_stride_length REAL NOT NULL,
+    avg_distance_rail REAL NOT NULL,
+    PRIMARY KEY (race_code, horse_code, section_number),
+    FOREIGN KEY (race_code, horse_code)
+        REFERENCES runners (race_code, horse_code) ON DELETE CASCADE
+);
+
+CREATE INDEX sections_horse_code ON sections (horse_code);
+
+CREATE TABLE speeds (
+    race_code INTEGER NOT NULL,
+    horse_code INTEGER NOT NULL,
+    point INTEGER NOT NULL,
+    distance INTEGER NOT NULL,
+    speed REAL NOT NULL,
+    PRIMARY KEY (race_code, horse_code, point),
+    FOREIGN KEY (race_code, horse_code)
+        REFERENCES runners (race_code, horse_code) ON DELETE CASCADE
+);
+
+CREATE TABLE ranks (
+    race_code INTEGER NOT NULL,
+    horse_code INTEGER NOT NULL,
+    point INTEGER NOT NULL,
+    distance INTEGER NOT NULL,
+    rank INTEGER NOT NULL,
+    PRIMARY KEY (race_code, horse_code, point),
+    FOREIGN KEY (race_code, horse_code)
+        REFERENCES runners (race_code, horse_code) ON DELETE CASCADE
+);
+
+CREATE TABLE fastest_sections (
+    race_code INTEGER NOT NULL REFERENCES races (race_code) ON DELETE CASCADE,
+    section_number INTEGER NOT NULL,
+    cumulated_distance INTEGER NOT NULL,
+    intermediate_time REAL NOT NULL,
+    section_time REAL NOT NULL,
+    PRIMARY KEY (race_code, section_number)
+);
@@ -0,0 +1,8 @@
+-- The leader's early and late splits (in seconds) and the pace they make,
+-- rated against the benchmarks of the races written with it.
+
+ALTER TABLE races ADD COLUMN early_split REAL;
+ALTER TABLE races ADD COLUMN late_split REAL;
+ALTER TABLE races ADD COLUMN pace TEXT;
+ALTER TABLE races ADD COLUMN early_pace_index REAL;
+ALTER TABLE races ADD COLUMN late_pace_index REAL;
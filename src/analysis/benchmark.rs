@@ -0,0 +1,461 @@
+use super::finishing::time_at;
+use super::stats::{finished, LATE_SPLIT, LATE_SPLITS};
+use crate::fields::seconds;
+use crate::structs::horsesummary::HorseSummary;
+use crate::structs::racesummary::RaceSummary;
+use chrono::NaiveTime;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+/// Percentiles kept for every benchmark, the 50th being the median.
+pub const PERCENTILES: [f64; 5] = [10.0, 25.0, 50.0, 75.0, 90.0];
+
+/// The going of a track condition, e.g. `Good` for `Good 4`.
+pub fn going(track_condition: &str) -> String {
+    track_condition
+        .split_whitespace()
+        .next()
+        .unwrap_or("Unknown")
+        .to_string()
+}
+
+/// The class of a race read from its name: `Group 1`, `Listed`, `BM65`,
+/// `Class 1`, `Maiden`, `Open`, or `Other` when the name gives none.
+pub fn race_class(race_name: &str) -> String {
+    let words: Vec<String> = race_name
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect();
+    let number_after = |i: usize| {
+        words
+            .get(i + 1)
+            .filter(|word| word.chars().all(|c| c.is_ascii_digit()))
+    };
+    for (i, word) in words.iter().enumerate() {
+        match word.as_str() {
+            "group" | "grp" => {
+                if let Some(n) = number_after(i) {
+                    return format!("Group {}", n);
+                }
+            }
+            "listed" => return "Listed".to_string(),
+            "bm" | "benchmark" => {
+                if let Some(n) = number_after(i) {
+                    return format!("BM{}", n);
+                }
+            }
+            "class" | "cl" => {
+                if let Some(n) = number_after(i) {
+                    return format!("Class {}", n);
+                }
+            }
+            "maiden" | "mdn" => return "Maiden".to_string(),
+            "open" | "quality" => return "Open".to_string(),
+            _ => {}
+        }
+        // Run together, as in `BM65`
+        if let Some(n) = word.strip_prefix("bm").filter(|n| n.parse::<u32>().is_ok()) {
+            return format!("BM{}", n);
+        }
+    }
+    "Other".to_string()
+}
+
+/// The races a benchmark is drawn from: same venue, distance, going and
+/// class.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BenchmarkKey {
+    pub venue: String,
+    pub distance: i32,
+    pub going: String,
+    pub class: String,
+}
+
+impl BenchmarkKey {
+    /// `None` when the race has no sections to give its distance.
+    pub fn of(race: &RaceSummary) -> Option<Self> {
+        Some(Self {
+            venue: race.course_name.clone(),
+            distance: race.distance()?,
+            going: going(&race.track_condition),
+            class: race_class(&race.race_name),
+        })
+    }
+}
+
+/// A time benchmarked for every finisher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Metric {
+    /// The runner's finish time
+    Finish,
+    /// Time to the mark `LATE_SPLIT` metres from the finish
+    Early,
+    /// Time over the last this many metres
+    Last(i32),
+    /// Time of the section starting this many metres from the finish
+    Section(i32),
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::Finish => write!(f, "finish"),
+            Metric::Early => write!(f, "early"),
+            Metric::Last(metres) => write!(f, "last_{}", metres),
+            Metric::Section(metres) => write!(f, "section_{}", metres),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let metres = |rest: &str| rest.parse().map_err(|_| format!("bad metric {:?}", text));
+        match text {
+            "finish" => Ok(Metric::Finish),
+            "early" => Ok(Metric::Early),
+            _ => match (text.strip_prefix("last_"), text.strip_prefix("section_")) {
+                (Some(rest), _) => metres(rest).map(Metric::Last),
+                (_, Some(rest)) => metres(rest).map(Metric::Section),
+                _ => Err(format!("bad metric {:?}", text)),
+            },
+        }
+    }
+}
+
+impl Serialize for Metric {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Metric {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+// Times in the feed are to the millisecond, and so are differences and
+// percentiles of them
+fn to_millis(seconds: f64) -> f64 {
+    (seconds * 1000.0).round() / 1000.0
+}
+
+fn recorded(time: NaiveTime) -> Option<f64> {
+    Some(seconds(&time)).filter(|seconds| *seconds > 0.0)
+}
+
+impl Metric {
+    /// Every metric of a runner with its time in seconds, or nothing for a
+    /// runner that did not finish. The time at the mark a late split starts
+    /// from, and so the early time to it, is read with [`time_at`].
+    pub fn times(race: &RaceSummary, horse: &HorseSummary) -> Vec<(Metric, f64)> {
+        let Some(distance) = race.distance().filter(|_| finished(horse)) else {
+            return Vec::new();
+        };
+        let mut times = Vec::new();
+        let finish = recorded(horse.finish_time);
+        if let Some(finish) = finish {
+            times.push((Metric::Finish, finish));
+        }
+        for metres in LATE_SPLITS {
+            let mark = time_at(horse, distance - metres)
+                .map(|(time, _)| time)
+                .filter(|time| *time > 0.0);
+            if let Some(mark) = mark.filter(|_| metres == LATE_SPLIT) {
+                times.push((Metric::Early, to_millis(mark)));
+            }
+            if let (Some(finish), Some(mark)) = (finish, mark) {
+                times.push((Metric::Last(metres), to_millis(finish - mark)));
+            }
+        }
+        for (from_finish, section) in horse.sections_from_finish(distance) {
+            if let Some(time) = recorded(section.section_time) {
+                times.push((Metric::Section(from_finish), time));
+            }
+        }
+        times
+    }
+}
+
+/// Percentiles of one metric over the finishers of a set of races. Times
+/// are in seconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Benchmark {
+    pub venue: String,
+    pub distance: i32,
+    pub going: String,
+    pub class: String,
+    pub metric: Metric,
+    // Number of times the percentiles are drawn from
+    pub count: usize,
+    pub p10: f64,
+    pub p25: f64,
+    pub median: f64,
+    pub p75: f64,
+    pub p90: f64,
+}
+
+// Linear interpolation between the closest ranks of sorted values, of which
+// there is at least one
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    to_millis(sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f64))
+}
+
+impl Benchmark {
+    pub fn key(&self) -> BenchmarkKey {
+        BenchmarkKey {
+            venue: self.venue.clone(),
+            distance: self.distance,
+            going: self.going.clone(),
+            class: self.class.clone(),
+        }
+    }
+
+    fn points(&self) -> [(f64, f64); 5] {
+        let values = [self.p10, self.p25, self.median, self.p75, self.p90];
+        let mut points = [(0.0, 0.0); 5];
+        for (point, (p, value)) in points.iter_mut().zip(PERCENTILES.iter().zip(values)) {
+            *point = (*p, value);
+        }
+        points
+    }
+
+    /// Where `time` falls among the benchmark's times, interpolated between
+    /// the stored percentiles and limited to 10 to 90. Lower is faster.
+    pub fn percentile_of(&self, time: f64) -> f64 {
+        let points = self.points();
+        let (first, last) = (points[0], points[points.len() - 1]);
+        if time <= first.1 {
+            return first.0;
+        }
+        if time >= last.1 {
+            return last.0;
+        }
+        points
+            .windows(2)
+            .find(|pair| time <= pair[1].1)
+            .map(|pair| {
+                let ((p0, t0), (p1, t1)) = (pair[0], pair[1]);
+                if t1 > t0 {
+                    p0 + (p1 - p0) * (time - t0) / (t1 - t0)
+                } else {
+                    p0
+                }
+            })
+            .unwrap_or(last.0)
+    }
+}
+
+/// A runner's time against its benchmark.
+#[derive(Debug, Clone)]
+pub struct Deviation<'a> {
+    pub metric: Metric,
+    pub time: f64,
+    pub benchmark: &'a Benchmark,
+}
+
+impl Deviation<'_> {
+    /// Seconds slower (positive) or faster (negative) than the median.
+    pub fn deviation(&self) -> f64 {
+        to_millis(self.time - self.benchmark.median)
+    }
+
+    pub fn percentile(&self) -> f64 {
+        self.benchmark.percentile_of(self.time)
+    }
+}
+
+/// Benchmarks of every metric for every venue, distance, going and class.
+#[derive(Debug, Clone, Default)]
+pub struct Benchmarks {
+    by_key: BTreeMap<(BenchmarkKey, Metric), Benchmark>,
+}
+
+impl Benchmarks {
+    /// Draws benchmarks from the finishers of an archive of races.
+    pub fn from_races(races: &[RaceSummary]) -> Self {
+        let mut samples: BTreeMap<(BenchmarkKey, Metric), Vec<f64>> = BTreeMap::new();
+        for race in races {
+            let Some(key) = BenchmarkKey::of(race) else {
+                continue;
+            };
+            for horse in &race.horses {
+                for (metric, time) in Metric::times(race, horse) {
+                    samples.entry((key.clone(), metric)).or_default().push(time);
+                }
+            }
+        }
+
+        let mut benchmarks = Self::default();
+        for ((key, metric), mut times) in samples {
+            times.sort_by(f64::total_cmp);
+            let [p10, p25, median, p75, p90] = PERCENTILES.map(|p| percentile(&times, p));
+            benchmarks.insert(Benchmark {
+                venue: key.venue,
+                distance: key.distance,
+                going: key.going,
+                class: key.class,
+                metric,
+                count: times.len(),
+                p10,
+                p25,
+                median,
+                p75,
+                p90,
+            });
+        }
+        benchmarks
+    }
+
+    pub fn insert(&mut self, benchmark: Benchmark) {
+        self.by_key
+            .insert((benchmark.key(), benchmark.metric), benchmark);
+    }
+
+    pub fn get(&self, key: &BenchmarkKey, metric: Metric) -> Option<&Benchmark> {
+        self.by_key.get(&(key.clone(), metric))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Benchmark> {
+        self.by_key.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_key.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_key.is_empty()
+    }
+
+    /// Every benchmarked time of a runner against its race's benchmarks.
+    pub fn deviations(&self, race: &RaceSummary, horse: &HorseSummary) -> Vec<Deviation<'_>> {
+        let Some(key) = BenchmarkKey::of(race) else {
+            return Vec::new();
+        };
+        Metric::times(race, horse)
+            .into_iter()
+            .filter_map(|(metric, time)| {
+                let benchmark = self.get(&key, metric)?;
+                Some(Deviation {
+                    metric,
+                    time,
+                    benchmark,
+                })
+            })
+            .collect()
+    }
+
+    /// Stores the benchmarks as CSV, one row per key and metric.
+    pub fn write_csv<W: Write>(&self, out: W) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut wtr = csv::Writer::from_writer(out);
+        for benchmark in self.iter() {
+            wtr.serialize(benchmark)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Reads benchmarks stored by `write_csv`.
+    pub fn read_csv<R: Read>(input: R) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut benchmarks = Self::default();
+        for benchmark in csv::Reader::from_reader(input).deserialize() {
+            benchmarks.insert(benchmark?);
+        }
+        Ok(benchmarks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{horse, race};
+
+    #[test]
+    fn percentile_interpolates_between_ranks() {
+        let sorted = [10.0, 20.0, 30.0, 40.0];
+        assert_eq!(percentile(&sorted, 0.0), 10.0);
+        assert_eq!(percentile(&sorted, 100.0), 40.0);
+        assert_eq!(percentile(&sorted, 50.0), 25.0);
+        assert_eq!(percentile(&sorted, 10.0), 13.0);
+    }
+
+    #[test]
+    fn percentile_of_one_time_is_that_time() {
+        for p in PERCENTILES {
+            assert_eq!(percentile(&[61.234], p), 61.234);
+        }
+    }
+
+    #[test]
+    fn race_class_reads_the_race_name() {
+        for (name, class) in [
+            ("Group 1 Stradbroke Handicap", "Group 1"),
+            ("Grp 3 Stakes", "Group 3"),
+            ("Listed Sprint", "Listed"),
+            ("BM 65 Handicap", "BM65"),
+            ("Benchmark 80", "BM80"),
+            ("Bm72 Handicap", "BM72"),
+            ("Class 1 Plate", "Class 1"),
+            ("Maiden Plate", "Maiden"),
+            ("Open Handicap", "Open"),
+            ("Fillies and Mares Handicap", "Other"),
+            ("Class Handicap", "Other"),
+            ("BM", "Other"),
+            ("", "Other"),
+        ] {
+            assert_eq!(race_class(name), class, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn times_of_a_runner_that_did_not_finish_are_empty() {
+        let race = race(vec![horse(0, &[(200, 12.0), (1000, 60.0)])]);
+        assert!(Metric::times(&race, &race.horses[0]).is_empty());
+    }
+
+    #[test]
+    fn times_without_sections_are_empty() {
+        let race = race(vec![horse(1, &[])]);
+        assert!(Metric::times(&race, &race.horses[0]).is_empty());
+    }
+
+    #[test]
+    fn late_splits_are_interpolated_between_unaligned_sections() {
+        // Boundaries at 300 and 700 m of 1000: the 400 and 600 m marks fall
+        // inside sections, the 200 m mark does not exist before 300 m
+        let race = race(vec![horse(1, &[(300, 18.0), (700, 42.0), (1000, 60.0)])]);
+        let times = Metric::times(&race, &race.horses[0]);
+        let get = |metric| times.iter().find(|(m, _)| *m == metric).map(|(_, t)| *t);
+        assert_eq!(get(Metric::Finish), Some(60.0));
+        assert_eq!(get(Metric::Early), Some(24.0));
+        assert_eq!(get(Metric::Last(200)), Some(12.0));
+        assert_eq!(get(Metric::Last(400)), Some(24.0));
+        assert_eq!(get(Metric::Last(600)), Some(36.0));
+        assert_eq!(get(Metric::Section(300)), Some(18.0));
+    }
+
+    #[test]
+    fn metric_names_round_trip() {
+        for metric in [
+            Metric::Finish,
+            Metric::Early,
+            Metric::Last(600),
+            Metric::Section(200),
+        ] {
+            assert_eq!(metric.to_string().parse::<Metric>(), Ok(metric));
+        }
+        assert!("last_".parse::<Metric>().is_err());
+        assert!("early_600".parse::<Metric>().is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use super::stats::finished;
+use crate::fields::seconds;
+use crate::structs::horsesummary::HorseSummary;
+use crate::structs::racesummary::RaceSummary;
+
+/// A runner's time at `cumulated_distance` metres from the start in
+/// seconds, and whether it was interpolated. Where the sections have no
+/// boundary there, the time is interpolated within the section containing
+/// it, assuming an even speed through that section.
+pub fn time_at(horse: &HorseSummary, cumulated_distance: i32) -> Option<(f64, bool)> {
+    let boundaries: Vec<(i32, f64)> = std::iter::once((0, 0.0))
+        .chain(horse.sections.iter().map(|section| {
+            (
+                section.cumulated_distance,
+                seconds(&section.intermediate_time),
+            )
+        }))
+        .collect();
+    if let Some((_, time)) = boundaries
+        .iter()
+        .find(|(distance, _)| *distance == cumulated_distance)
+    {
+        return Some((*time, false));
+    }
+    boundaries.windows(2).find_map(|pair| {
+        let ((d0, t0), (d1, t1)) = (pair[0], pair[1]);
+        if d0 < cumulated_distance && cumulated_distance < d1 && t1 > t0 {
+            let share = (cumulated_distance - d0) as f64 / (d1 - d0) as f64;
+            Some((t0 + (t1 - t0) * share, true))
+        } else {
+            None
+        }
+    })
+}
+
+/// Average speed over the last `metres` of a race as a percentage of the
+/// average speed over the whole race.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FinishingSpeed {
+    pub metres: i32,
+    pub percent: f64,
+    // The time at the start of the last `metres` fell inside a section
+    pub interpolated: bool,
+}
+
+/// The finishing speed of a runner over the last `metres`, from its section
+/// times and finish time. `None` for runners that did not finish or whose
+/// sections do not reach back that far.
+pub fn finishing_speed(
+    race: &RaceSummary,
+    horse: &HorseSummary,
+    metres: i32,
+) -> Option<FinishingSpeed> {
+    let distance = race.distance().filter(|_| finished(horse))?;
+    let finish = seconds(&horse.finish_time);
+    if metres <= 0 || metres >= distance || finish <= 0.0 {
+        return None;
+    }
+    let (mark, interpolated) = time_at(horse, distance - metres)?;
+    let late = finish - mark;
+    if late <= 0.0 || mark <= 0.0 {
+        return None;
+    }
+
+    let race_speed = distance as f64 / finish;
+    let late_speed = metres as f64 / late;
+    Some(FinishingSpeed {
+        metres,
+        percent: late_speed / race_speed * 100.0,
+        interpolated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{horse, race};
+
+    #[test]
+    fn time_at_a_boundary_is_exact() {
+        let horse = horse(1, &[(200, 12.0), (400, 24.0)]);
+        assert_eq!(time_at(&horse, 200), Some((12.0, false)));
+        assert_eq!(time_at(&horse, 0), Some((0.0, false)));
+    }
+
+    #[test]
+    fn time_at_is_interpolated_inside_a_section() {
+        let horse = horse(1, &[(300, 18.0), (700, 42.0)]);
+        assert_eq!(time_at(&horse, 500), Some((30.0, true)));
+        assert_eq!(time_at(&horse, 100), Some((6.0, true)));
+    }
+
+    #[test]
+    fn time_at_past_the_sections_is_none() {
+        assert_eq!(time_at(&horse(1, &[(200, 12.0)]), 300), None);
+        assert_eq!(time_at(&horse(1, &[]), 100), None);
+    }
+
+    #[test]
+    fn time_at_skips_a_section_without_a_time() {
+        // A section recorded as 00:00:00 after an earlier one cannot be
+        // interpolated into
+        let horse = horse(1, &[(200, 12.0), (400, 0.0)]);
+        assert_eq!(time_at(&horse, 300), None);
+    }
+
+    #[test]
+    fn finishing_speed_flags_an_interpolated_mark() {
+        let race = race(vec![horse(1, &[(300, 18.0), (700, 42.0), (1000, 60.0)])]);
+        let horse = &race.horses[0];
+        let speed = finishing_speed(&race, horse, 300).unwrap();
+        assert!(!speed.interpolated);
+        assert!((speed.percent - (300.0 / 18.0) / (1000.0 / 60.0) * 100.0).abs() < 1e-9);
+        assert!(finishing_speed(&race, horse, 600).unwrap().interpolated);
+    }
+
+    #[test]
+    fn finishing_speed_of_a_runner_that_did_not_finish_is_none() {
+        let race = race(vec![horse(0, &[(600, 36.0), (1000, 60.0)])]);
+        assert_eq!(finishing_speed(&race, &race.horses[0], 400), None);
+    }
+
+    #[test]
+    fn finishing_speed_over_the_whole_race_or_more_is_none() {
+        let race = race(vec![horse(1, &[(600, 36.0), (1000, 60.0)])]);
+        let horse = &race.horses[0];
+        assert_eq!(finishing_speed(&race, horse, 1000), None);
+        assert_eq!(finishing_speed(&race, horse, 0), None);
+    }
+}
@@ -0,0 +1,235 @@
+use super::stats::finished;
+use crate::fields::seconds;
+use crate::structs::horsesummary::HorseSummary;
+use crate::structs::racesummary::RaceSummary;
+use chrono::{Duration, NaiveTime};
+use std::collections::HashMap;
+
+/// Metres in a length, for margins and ground loss.
+pub const METRES_PER_LENGTH: f64 = 2.4;
+
+pub fn lengths(metres: f64) -> f64 {
+    metres / METRES_PER_LENGTH
+}
+
+/// Ground a runner lost in one section to the runner with the shortest trip
+/// through it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SectionGroundLoss {
+    pub cumulated_distance: i32,
+    // Metres further than the shortest trip
+    pub metres: f64,
+    // The extra metres at the runner's own speed through the section
+    pub seconds: f64,
+    // Metres further off the rail on average than the runner nearest it, when
+    // the runner's distance from the rail was recorded
+    pub wide: Option<f64>,
+}
+
+/// The ground every finisher lost in each of its sections against the other
+/// finishers' sections ending at the same distance, from their real
+/// distances, as `ground_loss` is against the finishers' trips. Keyed by
+/// horse code and the section's cumulated distance, and worked out in one
+/// pass over the race. Sections without a real distance or time are left
+/// out.
+pub fn section_ground_losses(race: &RaceSummary) -> HashMap<(i32, i32), SectionGroundLoss> {
+    let finishers = || race.horses.iter().filter(|horse| finished(horse));
+
+    // The shortest real distance and the distance nearest the rail through
+    // each section. A distance from the rail of 0 was not recorded rather
+    // than on the rail.
+    let mut best: HashMap<i32, (f64, f64)> = HashMap::new();
+    for section in finishers().flat_map(|horse| &horse.sections) {
+        if section.real_distance <= 0.0 {
+            continue;
+        }
+        let (shortest, nearest_rail) = best
+            .entry(section.cumulated_distance)
+            .or_insert((f64::INFINITY, f64::INFINITY));
+        *shortest = shortest.min(section.real_distance);
+        if section.avg_distance_rail > 0.0 {
+            *nearest_rail = nearest_rail.min(section.avg_distance_rail);
+        }
+    }
+
+    let mut losses = HashMap::new();
+    for horse in finishers() {
+        for section in &horse.sections {
+            let time = seconds(&section.section_time);
+            let Some((shortest, nearest_rail)) = best.get(&section.cumulated_distance) else {
+                continue;
+            };
+            if section.real_distance <= 0.0 || time <= 0.0 {
+                continue;
+            }
+            let metres = section.real_distance - shortest;
+            let speed = section.real_distance / time;
+            let wide = Some(section.avg_distance_rail)
+                .filter(|rail| *rail > 0.0)
+                .map(|rail| rail - nearest_rail);
+            losses.insert(
+                (horse.code, section.cumulated_distance),
+                SectionGroundLoss {
+                    cumulated_distance: section.cumulated_distance,
+                    metres,
+                    seconds: metres / speed,
+                    wide,
+                },
+            );
+        }
+    }
+    losses
+}
+
+/// Ground a runner lost over the whole race to the finisher with the
+/// shortest trip, and its finish time without it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GroundLoss {
+    pub metres: f64,
+    // The extra metres at the runner's average speed for the race
+    pub seconds: f64,
+    // The finish time less `seconds`: as if it had run the shortest trip
+    pub adjusted_finish_time: NaiveTime,
+}
+
+impl GroundLoss {
+    pub fn lengths(&self) -> f64 {
+        lengths(self.metres)
+    }
+}
+
+// Metres a runner travelled, from the race distance and its difference to it
+// when the distance travelled is missing
+fn trip(race: &RaceSummary, horse: &HorseSummary) -> Option<i32> {
+    if horse.distance_travelled > 0 {
+        return Some(horse.distance_travelled);
+    }
+    race.distance()
+        .filter(|_| horse.distance_difference != 0)
+        .map(|distance| distance + horse.distance_difference)
+}
+
+/// The ground loss of a finisher against the other finishers, from the
+/// distance each travelled. `None` for runners that did not finish or
+/// without a distance travelled.
+pub fn ground_loss(race: &RaceSummary, horse: &HorseSummary) -> Option<GroundLoss> {
+    let travelled = trip(race, horse).filter(|_| finished(horse))?;
+    let finish = seconds(&horse.finish_time);
+    if finish <= 0.0 {
+        return None;
+    }
+    let shortest = race
+        .horses
+        .iter()
+        .filter(|other| finished(other))
+        .filter_map(|other| trip(race, other))
+        .fold(travelled, i32::min);
+
+    let metres = (travelled - shortest) as f64;
+    let speed = travelled as f64 / finish;
+    let lost = metres / speed;
+    let adjusted = horse.finish_time - Duration::milliseconds((lost * 1000.0).round() as i64);
+    Some(GroundLoss {
+        metres,
+        seconds: lost,
+        adjusted_finish_time: adjusted,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{self, race, time};
+    use crate::fields::from_seconds;
+    use crate::structs::sectionsummary::SectionSummary;
+
+    // A runner without sections that travelled `travelled` metres in
+    // `finish` seconds
+    fn horse(final_rank: u8, travelled: i32, finish: f64) -> HorseSummary {
+        let mut horse = test_support::horse(final_rank, &[]);
+        horse.distance_travelled = travelled;
+        horse.finish_time = time(finish);
+        horse
+    }
+
+    // A 200 m section with its real distance, time and distance from the rail
+    fn section(real_distance: f64, seconds: f64, rail: f64) -> SectionSummary {
+        let mut section = SectionSummary::new();
+        section.cumulated_distance = 200;
+        section.real_distance = real_distance;
+        section.section_time = time(seconds);
+        section.avg_distance_rail = rail;
+        section
+    }
+
+    #[test]
+    fn ground_loss_is_against_the_shortest_finisher() {
+        // The runner that did not finish travelled least but does not count
+        let race = race(vec![
+            horse(1, 1000, 60.0),
+            horse(2, 1012, 60.72),
+            horse(0, 990, 0.0),
+        ]);
+        let loss = ground_loss(&race, &race.horses[1]).unwrap();
+        assert_eq!(loss.metres, 12.0);
+        assert_eq!(loss.lengths(), 5.0);
+        assert!((loss.seconds - 12.0 / (1012.0 / 60.72)).abs() < 1e-9);
+        assert_eq!(Some(loss.adjusted_finish_time), from_seconds(60.0));
+        assert_eq!(ground_loss(&race, &race.horses[0]).unwrap().metres, 0.0);
+    }
+
+    #[test]
+    fn ground_loss_of_a_runner_that_did_not_finish_is_none() {
+        let race = race(vec![horse(1, 1000, 60.0), horse(0, 1010, 61.0)]);
+        assert_eq!(ground_loss(&race, &race.horses[1]), None);
+    }
+
+    #[test]
+    fn ground_loss_without_a_trip_or_time_is_none() {
+        // No sections either, so no race distance to take a difference from
+        let race = race(vec![horse(1, 0, 60.0), horse(2, 1000, 0.0)]);
+        assert_eq!(ground_loss(&race, &race.horses[0]), None);
+        assert_eq!(ground_loss(&race, &race.horses[1]), None);
+    }
+
+    #[test]
+    fn wide_ignores_a_missing_distance_from_the_rail() {
+        let mut race = race(vec![horse(1, 1000, 60.0), horse(2, 1004, 61.0)]);
+        race.horses[0].add_section(section(200.0, 12.0, 0.0));
+        race.horses[1].add_section(section(203.0, 12.0, 3.5));
+
+        let losses = section_ground_losses(&race);
+        let wider = losses[&(2, 200)];
+        assert_eq!(wider.metres, 3.0);
+        assert_eq!(wider.wide, Some(0.0));
+        let missing = losses[&(1, 200)];
+        assert_eq!(missing.metres, 0.0);
+        assert_eq!(missing.wide, None);
+    }
+
+    #[test]
+    fn section_ground_loss_is_against_the_finishers() {
+        // The runner that pulled up hugged the rail on the shortest trip
+        let mut race = race(vec![
+            horse(1, 1000, 60.0),
+            horse(2, 1004, 61.0),
+            horse(0, 990, 0.0),
+        ]);
+        race.horses[0].add_section(section(201.0, 12.0, 2.0));
+        race.horses[1].add_section(section(203.0, 12.0, 4.0));
+        race.horses[2].add_section(section(198.0, 12.0, 1.0));
+
+        let losses = section_ground_losses(&race);
+        assert_eq!(losses[&(2, 200)].metres, 2.0);
+        assert_eq!(losses[&(2, 200)].wide, Some(2.0));
+        assert_eq!(losses.get(&(3, 200)), None);
+    }
+
+    #[test]
+    fn a_section_without_a_real_distance_or_time_has_no_ground_loss() {
+        let mut race = race(vec![horse(1, 1000, 60.0), horse(2, 1004, 61.0)]);
+        race.horses[0].add_section(section(0.0, 12.0, 2.0));
+        race.horses[1].add_section(section(200.0, 0.0, 2.0));
+        assert!(section_ground_losses(&race).is_empty());
+    }
+}
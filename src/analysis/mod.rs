@@ -0,0 +1,8 @@
+pub mod benchmark;
+pub mod finishing;
+pub mod ground;
+pub mod pace;
+pub mod stats;
+pub mod style;
+#[cfg(test)]
+mod test_support;
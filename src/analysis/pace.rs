@@ -0,0 +1,209 @@
+use super::benchmark::{BenchmarkKey, Benchmarks, Metric};
+use super::stats::{RaceStats, LATE_SPLIT};
+use crate::fields::seconds;
+use crate::structs::racesummary::RaceSummary;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Index points either side of par (100) still counted as run at par.
+pub const PACE_TOLERANCE: f64 = 1.0;
+
+/// The race fields set by `rate_pace`.
+pub const PACE_FIELDS: [&str; 5] = [
+    "early_split",
+    "late_split",
+    "pace",
+    "early_pace_index",
+    "late_pace_index",
+];
+
+/// How a race was run, from the leader's early and late splits against par.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pace {
+    /// At or above par throughout
+    Genuine,
+    /// Below par throughout
+    Moderate,
+    /// Slow early, then fast late
+    SlowThenSprint,
+    /// Fast early, then slow late
+    FastThenTiring,
+}
+
+impl Pace {
+    pub const ALL: [Pace; 4] = [
+        Pace::Genuine,
+        Pace::Moderate,
+        Pace::SlowThenSprint,
+        Pace::FastThenTiring,
+    ];
+
+    /// The pace of a race from its early and late pace indexes.
+    pub fn of(early_index: f64, late_index: f64) -> Self {
+        let (fast, slow) = (100.0 + PACE_TOLERANCE, 100.0 - PACE_TOLERANCE);
+        if early_index < slow && late_index > fast {
+            Pace::SlowThenSprint
+        } else if early_index > fast && late_index < slow {
+            Pace::FastThenTiring
+        } else if (early_index + late_index) / 2.0 >= 100.0 {
+            Pace::Genuine
+        } else {
+            Pace::Moderate
+        }
+    }
+}
+
+impl fmt::Display for Pace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Pace::Genuine => "genuine",
+            Pace::Moderate => "moderate",
+            Pace::SlowThenSprint => "slow_then_sprint",
+            Pace::FastThenTiring => "fast_then_tiring",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Pace {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Pace::ALL
+            .into_iter()
+            .find(|pace| pace.to_string() == text)
+            .ok_or_else(|| format!("bad pace {:?}", text))
+    }
+}
+
+impl Serialize for Pace {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pace {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// A race's early and late pace as a percentage of par speed, above 100
+/// being faster than par, and the pace they make. As the splits are run by
+/// the front of the field, par is the 10th percentile of the benchmark
+/// rather than its median.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaceRating {
+    // The leader's time to the `LATE_SPLIT` mark against its par
+    pub early_index: f64,
+    // The same leader's last `LATE_SPLIT` metres against their par
+    pub late_index: f64,
+    pub pace: Pace,
+}
+
+impl PaceRating {
+    /// Rates the splits of `stats` against the race's benchmarks, or `None`
+    /// when either split or its par is missing.
+    pub fn new(race: &RaceSummary, stats: &RaceStats, benchmarks: &Benchmarks) -> Option<Self> {
+        let key = BenchmarkKey::of(race)?;
+        let par_early = benchmarks.get(&key, Metric::Early)?.p10;
+        let par_late = benchmarks.get(&key, Metric::Last(LATE_SPLIT))?.p10;
+        let early = seconds(&stats.early_split?);
+        let late = seconds(&stats.late_split?);
+        if par_early <= 0.0 || par_late <= 0.0 || early <= 0.0 || late <= 0.0 {
+            return None;
+        }
+
+        // Over the same distance speed is inversely proportional to time
+        let early_index = par_early / early * 100.0;
+        let late_index = par_late / late * 100.0;
+        Some(Self {
+            early_index,
+            late_index,
+            pace: Pace::of(early_index, late_index),
+        })
+    }
+}
+
+/// Sets the leader's early and late splits of every race and, where the race
+/// has benchmarks, its pace rated against them (see `PaceRating`).
+pub fn rate_pace(races: &mut [RaceSummary], benchmarks: &Benchmarks) {
+    for race in races {
+        let stats = RaceStats::new(race);
+        let rating = PaceRating::new(race, &stats, benchmarks);
+        let (early_split, late_split) = (stats.early_split, stats.late_split);
+        race.early_split = early_split;
+        race.late_split = late_split;
+        race.pace = rating.map(|rating| rating.pace);
+        race.early_pace_index = rating.map(|rating| rating.early_index);
+        race.late_pace_index = rating.map(|rating| rating.late_index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{self, horse};
+    use crate::fields::from_seconds;
+
+    // A winning runner over 1000 m reaching the 400 m mark at `early` and
+    // finishing at `finish`
+    fn race(early: f64, finish: f64) -> RaceSummary {
+        test_support::race(vec![horse(1, &[(400, early), (1000, finish)])])
+    }
+
+    #[test]
+    fn pace_of_indexes() {
+        assert_eq!(Pace::of(97.0, 103.0), Pace::SlowThenSprint);
+        assert_eq!(Pace::of(103.0, 97.0), Pace::FastThenTiring);
+        assert_eq!(Pace::of(100.5, 99.5), Pace::Genuine);
+        assert_eq!(Pace::of(99.5, 100.2), Pace::Moderate);
+        // Within the tolerance of par neither split counts as slow or fast
+        assert_eq!(Pace::of(99.0, 101.0), Pace::Genuine);
+        assert_eq!(Pace::of(101.0, 98.0), Pace::Moderate);
+    }
+
+    #[test]
+    fn pace_names_round_trip() {
+        for pace in Pace::ALL {
+            assert_eq!(pace.to_string().parse::<Pace>(), Ok(pace));
+        }
+        assert!("fast".parse::<Pace>().is_err());
+    }
+
+    #[test]
+    fn splits_are_rated_against_their_own_pars() {
+        let benchmarks = Benchmarks::from_races(&[race(24.0, 60.0)]);
+        let mut races = [race(22.0, 60.0)];
+        rate_pace(&mut races, &benchmarks);
+        let race = &races[0];
+        assert_eq!(race.early_split, from_seconds(22.0));
+        assert_eq!(race.late_split, from_seconds(38.0));
+        assert!((race.early_pace_index.unwrap() - 24.0 / 22.0 * 100.0).abs() < 1e-9);
+        assert!((race.late_pace_index.unwrap() - 36.0 / 38.0 * 100.0).abs() < 1e-9);
+        assert_eq!(race.pace, Some(Pace::FastThenTiring));
+    }
+
+    #[test]
+    fn a_race_at_par_is_genuine() {
+        let mut races = [race(24.0, 60.0)];
+        let benchmarks = Benchmarks::from_races(&races);
+        rate_pace(&mut races, &benchmarks);
+        assert_eq!(races[0].early_pace_index, Some(100.0));
+        assert_eq!(races[0].late_pace_index, Some(100.0));
+        assert_eq!(races[0].pace, Some(Pace::Genuine));
+    }
+
+    #[test]
+    fn a_race_without_benchmarks_keeps_its_splits() {
+        let mut races = [race(24.0, 60.0)];
+        rate_pace(&mut races, &Benchmarks::default());
+        assert_eq!(races[0].early_split, from_seconds(24.0));
+        assert_eq!(races[0].late_split, from_seconds(36.0));
+        assert_eq!(races[0].pace, None);
+        assert_eq!(races[0].early_pace_index, None);
+    }
+}
@@ -0,0 +1,113 @@
+use super::finishing::time_at;
+use crate::fields::{from_seconds, seconds};
+use crate::structs::horsesummary::HorseSummary;
+use crate::structs::racesummary::RaceSummary;
+use chrono::NaiveTime;
+
+/// Metres from the finish splitting a race into its early and late parts.
+pub const LATE_SPLIT: i32 = 600;
+
+/// Metres from the finish of the late splits benchmarked and rated for every
+/// race.
+pub const LATE_SPLITS: [i32; 3] = [600, 400, 200];
+
+/// Whether a runner finished the race, i.e. was given a placing.
+pub fn finished(horse: &HorseSummary) -> bool {
+    horse.final_rank > 0
+}
+
+/// Aggregates of a whole race, computed from its runners.
+#[derive(Debug, Clone)]
+pub struct RaceStats<'a> {
+    pub distance: Option<i32>,
+    pub field_size: usize,
+    pub finishers: usize,
+    pub winner: Option<&'a HorseSummary>,
+    pub winning_time: Option<NaiveTime>,
+    // The leader's time to the `LATE_SPLIT` mark
+    pub early_split: Option<NaiveTime>,
+    // The same leader's time over the last `LATE_SPLIT` metres, when it finished
+    pub late_split: Option<NaiveTime>,
+}
+
+impl<'a> RaceStats<'a> {
+    pub fn new(race: &'a RaceSummary) -> Self {
+        let distance = race.distance();
+        let winner = race.horses.iter().find(|horse| horse.final_rank == 1);
+        let winning_time = winner.map(|horse| horse.finish_time);
+        let leader = distance.and_then(|distance| leader_at(race, distance - LATE_SPLIT));
+        let early_split = leader.and_then(|(_, mark)| from_seconds(mark));
+        let late_split = leader
+            .filter(|(horse, _)| finished(horse))
+            .map(|(horse, mark)| seconds(&horse.finish_time) - mark)
+            .filter(|late| *late > 0.0)
+            .and_then(from_seconds);
+
+        Self {
+            distance,
+            field_size: race.horses.len(),
+            finishers: race.horses.iter().filter(|horse| finished(horse)).count(),
+            winner,
+            winning_time,
+            early_split,
+            late_split,
+        }
+    }
+}
+
+/// The first runner to reach `cumulated_distance` metres from the start and
+/// its time there in seconds, read with [`time_at`].
+pub fn leader_at(race: &RaceSummary, cumulated_distance: i32) -> Option<(&HorseSummary, f64)> {
+    race.horses
+        .iter()
+        .filter_map(|horse| Some((horse, time_at(horse, cumulated_distance)?.0)))
+        .filter(|(_, time)| *time > 0.0)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{horse, race};
+
+    #[test]
+    fn late_split_is_the_leaders_own() {
+        // The leader at the 400 m mark fades to second behind a closer
+        let race = race(vec![
+            horse(2, &[(400, 22.0), (1000, 58.0)]),
+            horse(1, &[(400, 24.0), (1000, 57.0)]),
+        ]);
+        let stats = RaceStats::new(&race);
+        assert_eq!(stats.early_split, from_seconds(22.0));
+        assert_eq!(stats.late_split, from_seconds(36.0));
+        assert_eq!(stats.winning_time, from_seconds(57.0));
+    }
+
+    #[test]
+    fn a_leader_that_did_not_finish_has_no_late_split() {
+        let race = race(vec![
+            horse(0, &[(400, 22.0), (1000, 58.0)]),
+            horse(1, &[(400, 24.0), (1000, 57.0)]),
+        ]);
+        let stats = RaceStats::new(&race);
+        assert_eq!(stats.early_split, from_seconds(22.0));
+        assert_eq!(stats.late_split, None);
+        assert_eq!(stats.finishers, 1);
+    }
+
+    #[test]
+    fn the_mark_is_interpolated_for_unaligned_sections() {
+        let race = race(vec![horse(1, &[(300, 18.0), (700, 42.0), (1000, 60.0)])]);
+        let stats = RaceStats::new(&race);
+        assert_eq!(stats.early_split, from_seconds(24.0));
+        assert_eq!(stats.late_split, from_seconds(36.0));
+    }
+
+    #[test]
+    fn a_race_without_sections_has_no_splits() {
+        let race = race(vec![horse(1, &[])]);
+        let stats = RaceStats::new(&race);
+        assert_eq!(stats.distance, None);
+        assert_eq!((stats.early_split, stats.late_split), (None, None));
+    }
+}
@@ -0,0 +1,299 @@
+use super::stats::LATE_SPLIT;
+use crate::structs::horsesummary::HorseSummary;
+use crate::structs::racesummary::RaceSummary;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Where a runner raced in the early part of a race.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RunStyle {
+    /// Led, or raced within half a place of the lead on average
+    Leader,
+    /// In the first third of the field behind the leader
+    OnPace,
+    /// In the middle third of the field
+    Midfield,
+    /// In the last third of the field
+    Backmarker,
+}
+
+impl RunStyle {
+    pub const ALL: [RunStyle; 4] = [
+        RunStyle::Leader,
+        RunStyle::OnPace,
+        RunStyle::Midfield,
+        RunStyle::Backmarker,
+    ];
+
+    /// The style of a runner's run from its early position, or `None` when
+    /// neither its sections nor its rank series give one. The field is the
+    /// runners with an early position, so non-runners do not stretch it.
+    pub fn of(race: &RaceSummary, horse: &HorseSummary) -> Option<Self> {
+        let position = early_position(race, horse)?;
+        let field_size = race
+            .horses
+            .iter()
+            .filter(|h| early_position(race, h).is_some())
+            .count();
+        if position <= 1.5 || field_size <= 1 {
+            return Some(RunStyle::Leader);
+        }
+        // 0 for the front of the field, 1 for the back
+        let relative = (position - 1.0) / (field_size - 1) as f64;
+        Some(if relative <= 1.0 / 3.0 {
+            RunStyle::OnPace
+        } else if relative <= 2.0 / 3.0 {
+            RunStyle::Midfield
+        } else {
+            RunStyle::Backmarker
+        })
+    }
+}
+
+impl fmt::Display for RunStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RunStyle::Leader => "leader",
+            RunStyle::OnPace => "on_pace",
+            RunStyle::Midfield => "midfield",
+            RunStyle::Backmarker => "backmarker",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for RunStyle {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        RunStyle::ALL
+            .into_iter()
+            .find(|style| style.to_string() == text)
+            .ok_or_else(|| format!("bad run style {:?}", text))
+    }
+}
+
+/// A runner's average rank before the last `LATE_SPLIT` metres, from the
+/// ranks of its sections ending there and the points of its rank series.
+/// Races no longer than `LATE_SPLIT` use their first section. The rank at
+/// the start is left out, as the field has not settled.
+pub fn early_position(race: &RaceSummary, horse: &HorseSummary) -> Option<f64> {
+    let distance = race.distance()?;
+    let first = horse.sections.first()?.cumulated_distance;
+    let early_end = (distance - LATE_SPLIT).max(first);
+
+    let sections = horse
+        .sections
+        .iter()
+        .filter(|section| section.cumulated_distance <= early_end && section.rank > 0)
+        .map(|section| section.rank as f64);
+    let series = horse
+        .ranks
+        .iter()
+        .filter(|(at, rank)| *at > 0 && *at <= early_end && *rank > 0.0)
+        .map(|(_, rank)| *rank);
+    let ranks: Vec<f64> = sections.chain(series).collect();
+    if ranks.is_empty() {
+        return None;
+    }
+    Some(ranks.iter().sum::<f64>() / ranks.len() as f64)
+}
+
+/// How often a horse has raced in each style.
+#[derive(Debug, Clone)]
+pub struct StyleProfile {
+    pub code: i32,
+    // Name at the horse's latest run
+    pub name: String,
+    pub leader: usize,
+    pub on_pace: usize,
+    pub midfield: usize,
+    pub backmarker: usize,
+}
+
+impl StyleProfile {
+    fn new(code: i32, name: &str) -> Self {
+        Self {
+            code,
+            name: name.to_string(),
+            leader: 0,
+            on_pace: 0,
+            midfield: 0,
+            backmarker: 0,
+        }
+    }
+
+    pub fn count(&self, style: RunStyle) -> usize {
+        match style {
+            RunStyle::Leader => self.leader,
+            RunStyle::OnPace => self.on_pace,
+            RunStyle::Midfield => self.midfield,
+            RunStyle::Backmarker => self.backmarker,
+        }
+    }
+
+    fn add(&mut self, style: RunStyle) {
+        let count = match style {
+            RunStyle::Leader => &mut self.leader,
+            RunStyle::OnPace => &mut self.on_pace,
+            RunStyle::Midfield => &mut self.midfield,
+            RunStyle::Backmarker => &mut self.backmarker,
+        };
+        *count += 1;
+    }
+
+    /// Runs with a style.
+    pub fn runs(&self) -> usize {
+        RunStyle::ALL.iter().map(|style| self.count(*style)).sum()
+    }
+
+    /// Fraction of the horse's runs in `style`.
+    pub fn share(&self, style: RunStyle) -> f64 {
+        match self.runs() {
+            0 => 0.0,
+            runs => self.count(style) as f64 / runs as f64,
+        }
+    }
+
+    /// The style the horse has raced in most often, the more forward one on
+    /// a tie.
+    pub fn usual(&self) -> Option<RunStyle> {
+        RunStyle::ALL
+            .into_iter()
+            .filter(|style| self.count(*style) > 0)
+            .max_by_key(|style| (self.count(*style), std::cmp::Reverse(*style)))
+    }
+}
+
+/// Style profiles of every horse in a set of races, by horse code.
+#[derive(Debug, Clone, Default)]
+pub struct StyleProfiles {
+    by_code: BTreeMap<i32, StyleProfile>,
+}
+
+impl StyleProfiles {
+    /// Counts the style of every run in `races`, which should be in date
+    /// order for the names to be the latest.
+    pub fn from_races(races: &[RaceSummary]) -> Self {
+        let mut profiles = Self::default();
+        for race in races {
+            for horse in &race.horses {
+                let Some(style) = RunStyle::of(race, horse) else {
+                    continue;
+                };
+                let profile = profiles
+                    .by_code
+                    .entry(horse.code)
+                    .or_insert_with(|| StyleProfile::new(horse.code, &horse.name));
+                profile.name.clone_from(&horse.name);
+                profile.add(style);
+            }
+        }
+        profiles
+    }
+
+    pub fn get(&self, code: i32) -> Option<&StyleProfile> {
+        self.by_code.get(&code)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &StyleProfile> {
+        self.by_code.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_code.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_code.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support;
+
+    // A runner over 1000 m with the given rank at each 200 m section
+    fn horse(ranks: &[i32]) -> HorseSummary {
+        let boundaries: Vec<(i32, f64)> = (1..=ranks.len())
+            .map(|i| (200 * i as i32, 12.0 * i as f64))
+            .collect();
+        let mut horse = test_support::horse(1, &boundaries);
+        for (section, rank) in horse.sections.iter_mut().zip(ranks) {
+            section.rank = *rank;
+        }
+        horse
+    }
+
+    // A race of `field_size` runners, the first of which has `ranks`
+    fn race(field_size: usize, ranks: &[i32]) -> RaceSummary {
+        let mut horses = vec![horse(ranks)];
+        horses.resize_with(field_size, || horse(&[1, 1, 1, 1, 1]));
+        test_support::race(horses)
+    }
+
+    fn style(field_size: usize, ranks: &[i32]) -> Option<RunStyle> {
+        let race = race(field_size, ranks);
+        RunStyle::of(&race, &race.horses[0])
+    }
+
+    #[test]
+    fn only_ranks_before_the_late_split_count() {
+        // The early part of 1000 m ends at 400 m
+        let race = race(7, &[2, 4, 7, 7, 7]);
+        assert_eq!(early_position(&race, &race.horses[0]), Some(3.0));
+    }
+
+    #[test]
+    fn thresholds_split_the_field_in_thirds() {
+        assert_eq!(style(7, &[1, 2, 5, 5, 5]), Some(RunStyle::Leader));
+        assert_eq!(style(7, &[2, 2, 1, 1, 1]), Some(RunStyle::OnPace));
+        assert_eq!(style(7, &[3, 3, 1, 1, 1]), Some(RunStyle::OnPace));
+        assert_eq!(style(7, &[5, 5, 1, 1, 1]), Some(RunStyle::Midfield));
+        assert_eq!(style(7, &[6, 6, 1, 1, 1]), Some(RunStyle::Backmarker));
+    }
+
+    #[test]
+    fn non_runners_are_not_in_the_field() {
+        // Sixth of 7 runners, with 3 non-runners that have no sections
+        let mut race = race(7, &[6, 6, 1, 1, 1]);
+        for _ in 0..3 {
+            race.add_horse(HorseSummary::new());
+        }
+        assert_eq!(
+            RunStyle::of(&race, &race.horses[0]),
+            Some(RunStyle::Backmarker)
+        );
+        assert_eq!(RunStyle::of(&race, &race.horses[7]), None);
+    }
+
+    #[test]
+    fn a_lone_runner_leads() {
+        assert_eq!(style(1, &[3, 3, 3, 3, 3]), Some(RunStyle::Leader));
+    }
+
+    #[test]
+    fn missing_ranks_are_ignored() {
+        assert_eq!(style(7, &[0, 6, 1, 1, 1]), Some(RunStyle::Backmarker));
+        assert_eq!(style(7, &[0, 0, 1, 1, 1]), None);
+    }
+
+    #[test]
+    fn a_runner_without_sections_has_no_style() {
+        assert_eq!(style(7, &[]), None);
+        // Nor does one in a race without a distance
+        let mut race = RaceSummary::new();
+        race.add_horse(HorseSummary::new());
+        assert_eq!(RunStyle::of(&race, &race.horses[0]), None);
+    }
+
+    #[test]
+    fn style_names_round_trip() {
+        for style in RunStyle::ALL {
+            assert_eq!(style.to_string().parse::<RunStyle>(), Ok(style));
+        }
+        assert!("front".parse::<RunStyle>().is_err());
+    }
+}
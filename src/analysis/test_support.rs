@@ -0,0 +1,40 @@
+//! Runners and races for the analysis tests.
+
+use crate::fields::from_seconds;
+use crate::structs::horsesummary::HorseSummary;
+use crate::structs::racesummary::RaceSummary;
+use crate::structs::sectionsummary::SectionSummary;
+use chrono::NaiveTime;
+
+pub fn time(seconds: f64) -> NaiveTime {
+    from_seconds(seconds).unwrap()
+}
+
+/// A runner with sections ending at `boundaries` (metres, seconds),
+/// finishing at the last of them. A boundary at 0 s is a section without a
+/// time.
+pub fn horse(final_rank: u8, boundaries: &[(i32, f64)]) -> HorseSummary {
+    let mut horse = HorseSummary::new();
+    horse.final_rank = final_rank;
+    let mut previous = 0.0;
+    for &(distance, at) in boundaries {
+        let mut section = SectionSummary::new();
+        section.cumulated_distance = distance;
+        section.intermediate_time = time(at);
+        section.section_time = time(if at > 0.0 { at - previous } else { 0.0 });
+        horse.add_section(section);
+        horse.finish_time = time(at);
+        previous = at;
+    }
+    horse
+}
+
+/// A race of `horses`, their codes numbered from 1 in order.
+pub fn race(horses: Vec<HorseSummary>) -> RaceSummary {
+    let mut race = RaceSummary::new();
+    for (i, mut horse) in horses.into_iter().enumerate() {
+        horse.code = i as i32 + 1;
+        race.add_horse(horse);
+    }
+    race
+}
@@ -0,0 +1,181 @@
+use crate::error::{ErrorKind, ParseError};
+use crate::extract::{check_entry_count, copy_entry, ZipLimits};
+use crate::structs::horsesummary::HorseSummary;
+use crate::structs::racesummary::RaceSummary;
+use crate::{files_under, parse_file, parse_xml, race_number_from_path};
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+/// The outcome of parsing one document.
+#[derive(Debug)]
+pub struct ParseResult {
+    pub path: PathBuf,
+    pub parsed: Result<RaceSummary, ParseError>,
+    // Content of a failed document that is not a file of its own, such as a
+    // zip entry or standard input, kept so that it can be quarantined
+    pub bytes: Option<Vec<u8>>,
+}
+
+impl ParseResult {
+    fn new(path: PathBuf, parsed: Result<RaceSummary, ParseError>, bytes: &[u8]) -> Self {
+        let bytes = parsed.is_err().then(|| bytes.to_vec());
+        Self {
+            path,
+            parsed,
+            bytes,
+        }
+    }
+
+    fn of_file(path: PathBuf, parsed: Result<RaceSummary, ParseError>) -> Self {
+        Self {
+            path,
+            parsed,
+            bytes: None,
+        }
+    }
+}
+
+// Local file header signature every zip archive starts with
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Path used for standard input in reports.
+pub const STDIN: &str = "-";
+
+/// Parses every file in parallel. Results come back in the order of `paths`
+/// whatever order the threads finish in.
+pub fn parse_files(paths: &[PathBuf]) -> Vec<ParseResult> {
+    paths
+        .par_iter()
+        .map(|path| ParseResult::of_file(path.clone(), parse_file(path)))
+        .collect()
+}
+
+pub fn is_zip(bytes: &[u8]) -> bool {
+    bytes.starts_with(ZIP_MAGIC)
+}
+
+/// Parses every XML entry of an in-memory zip archive. Entries are reported
+/// as `<label>/<entry name>`.
+pub fn parse_zip(
+    label: &Path,
+    bytes: &[u8],
+    limits: &ZipLimits,
+) -> Result<Vec<ParseResult>, ParseError> {
+    let limit_error = |e: io::Error| ParseError::new(ErrorKind::Zip, e.to_string());
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+    check_entry_count(&archive, limits).map_err(limit_error)?;
+
+    let mut total = 0;
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = match file.enclosed_name() {
+            Some(name) if file.is_file() => name.to_owned(),
+            _ => continue,
+        };
+        let is_xml = name
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("xml"));
+        if !is_xml {
+            continue;
+        }
+        let mut data = Vec::new();
+        copy_entry(&mut file, &mut data, limits, &mut total).map_err(limit_error)?;
+        entries.push((name, data));
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(entries
+        .par_iter()
+        .map(|(name, data)| {
+            let parsed =
+                race_number_from_path(name).and_then(|number| parse_xml(&data[..], number));
+            ParseResult::new(label.join(name), parsed, data)
+        })
+        .collect())
+}
+
+/// Parses bytes holding either a zip archive or a single XML document. A lone
+/// document takes its race number from `name` when one is given.
+pub fn parse_bytes(
+    label: &Path,
+    bytes: &[u8],
+    name: Option<&Path>,
+    limits: &ZipLimits,
+) -> Result<Vec<ParseResult>, ParseError> {
+    if is_zip(bytes) {
+        return parse_zip(label, bytes, limits);
+    }
+    let parsed = match name {
+        Some(name) => race_number_from_path(name).and_then(|number| parse_xml(bytes, number)),
+        None => parse_xml(bytes, 0),
+    };
+    Ok(vec![ParseResult::new(label.to_path_buf(), parsed, bytes)])
+}
+
+/// Parses a directory of XML files, a zip archive, a single XML file or, for
+/// `-`, whatever arrives on standard input.
+pub fn parse_input(
+    input: &Path,
+    stdin_name: Option<&Path>,
+    limits: &ZipLimits,
+) -> Vec<ParseResult> {
+    if input == Path::new(STDIN) {
+        let mut bytes = Vec::new();
+        let parsed = io::stdin()
+            .lock()
+            .read_to_end(&mut bytes)
+            .map_err(ParseError::from)
+            .and_then(|_| parse_bytes(input, &bytes, stdin_name, limits));
+        return parsed
+            .unwrap_or_else(|e| vec![ParseResult::new(input.to_path_buf(), Err(e), &bytes)]);
+    }
+
+    let parsed = if input.is_dir() {
+        files_under(input)
+            .map(|paths| parse_files(&paths))
+            .map_err(ParseError::from)
+    } else if input
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+    {
+        fs::read(input)
+            .map_err(ParseError::from)
+            .and_then(|bytes| parse_zip(input, &bytes, limits))
+    } else {
+        Ok(parse_files(&[input.to_path_buf()]))
+    };
+
+    parsed.unwrap_or_else(|e| vec![ParseResult::of_file(input.to_path_buf(), Err(e))])
+}
+
+/// Orders runners by final rank, with unplaced runners (rank 0) last.
+pub fn compare_horses(a: &HorseSummary, b: &HorseSummary) -> Ordering {
+    (a.final_rank == 0, a.final_rank, a.bib, a.code).cmp(&(
+        b.final_rank == 0,
+        b.final_rank,
+        b.bib,
+        b.code,
+    ))
+}
+
+/// Orders races by event date, venue and race number.
+pub fn compare_races(a: &RaceSummary, b: &RaceSummary) -> Ordering {
+    (a.event_date, &a.course_name, a.race_number, a.race_code).cmp(&(
+        b.event_date,
+        &b.course_name,
+        b.race_number,
+        b.race_code,
+    ))
+}
+
+/// Puts races and their runners into the stable output order.
+pub fn sort_races(races: &mut [RaceSummary]) {
+    races.par_sort_by(compare_races);
+    for race in races.iter_mut() {
+        race.horses.sort_by(compare_horses);
+    }
+}
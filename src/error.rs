@@ -0,0 +1,66 @@
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Io,
+    Xml,
+    Encoding,
+    Zip,
+    InvalidValue,
+    MissingRaceCode,
+    FileName,
+    Database,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseError {
+    pub kind: ErrorKind,
+    pub message: String,
+    // Slash separated path of the open elements, e.g. RaceSummary/Horses/HorseSummary
+    pub element_path: Option<String>,
+}
+
+impl ParseError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            element_path: None,
+        }
+    }
+
+    /// Records where in the document the error happened, keeping any path
+    /// that was already set closer to the failure.
+    pub fn at(mut self, elements: &[String]) -> Self {
+        if self.element_path.is_none() && !elements.is_empty() {
+            self.element_path = Some(elements.join("/"));
+        }
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.element_path {
+            Some(path) => write!(f, "{:?} at {}: {}", self.kind, path, self.message),
+            None => write!(f, "{:?}: {}", self.kind, self.message),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<std::io::Error> for ParseError {
+    fn from(e: std::io::Error) -> Self {
+        ParseError::new(ErrorKind::Io, e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for ParseError {
+    fn from(e: zip::result::ZipError) -> Self {
+        ParseError::new(ErrorKind::Zip, e.to_string())
+    }
+}
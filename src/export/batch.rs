@@ -0,0 +1,142 @@
+use super::long::sections_table;
+use super::table::Table;
+use super::tables::{normalised_tables, runners_table, TableOptions};
+use crate::fields::{FieldKind, FieldValue};
+use crate::structs::racesummary::RaceSummary;
+use arrow::array::{
+    ArrayRef, BooleanBuilder, Date32Builder, DurationMillisecondBuilder, Float64Builder,
+    Int64Builder, StringBuilder,
+};
+use arrow::datatypes::{DataType, Date32Type, Field, Schema, SchemaRef, TimeUnit};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use chrono::NaiveTime;
+use std::sync::Arc;
+
+/// Arrow type of each field kind. Times in the feed are elapsed race times
+/// rather than times of day, so they become millisecond durations.
+pub fn data_type(kind: FieldKind) -> DataType {
+    match kind {
+        FieldKind::Bool => DataType::Boolean,
+        FieldKind::Int => DataType::Int64,
+        FieldKind::Float => DataType::Float64,
+        FieldKind::Text => DataType::Utf8,
+        FieldKind::Date => DataType::Date32,
+        FieldKind::Time => DataType::Duration(TimeUnit::Millisecond),
+    }
+}
+
+pub fn arrow_schema(table: &Table) -> SchemaRef {
+    let fields: Vec<Field> = table
+        .columns
+        .iter()
+        .map(|column| Field::new(&column.name, data_type(column.kind), true))
+        .collect();
+    Arc::new(Schema::new(fields))
+}
+
+fn millis(time: &NaiveTime) -> i64 {
+    (*time - NaiveTime::MIN).num_milliseconds()
+}
+
+/// Builds one column, nulls for missing values and values of another kind.
+fn column_array(table: &Table, index: usize) -> ArrayRef {
+    let values = table.rows.iter().map(|row| &row[index]);
+    match table.columns[index].kind {
+        FieldKind::Bool => {
+            let mut builder = BooleanBuilder::new();
+            for value in values {
+                builder.append_option(match value {
+                    FieldValue::Bool(value) => Some(*value),
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        }
+        FieldKind::Int => {
+            let mut builder = Int64Builder::new();
+            for value in values {
+                builder.append_option(match value {
+                    FieldValue::Int(value) => Some(*value),
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        }
+        FieldKind::Float => {
+            let mut builder = Float64Builder::new();
+            for value in values {
+                builder.append_option(value.as_f64());
+            }
+            Arc::new(builder.finish())
+        }
+        FieldKind::Text => {
+            let mut builder = StringBuilder::new();
+            for value in values {
+                match value {
+                    FieldValue::Null => builder.append_null(),
+                    value => builder.append_value(value.to_string()),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        FieldKind::Date => {
+            let mut builder = Date32Builder::new();
+            for value in values {
+                builder.append_option(match value {
+                    FieldValue::Date(value) => Some(Date32Type::from_naive_date(*value)),
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        }
+        FieldKind::Time => {
+            let mut builder = DurationMillisecondBuilder::new();
+            for value in values {
+                builder.append_option(match value {
+                    FieldValue::Time(value) => Some(millis(value)),
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        }
+    }
+}
+
+/// Converts a table into a single Arrow record batch with typed columns.
+pub fn record_batch(table: &Table) -> Result<RecordBatch, ArrowError> {
+    let columns = (0..table.columns.len())
+        .map(|index| column_array(table, index))
+        .collect();
+    RecordBatch::try_new(arrow_schema(table), columns)
+}
+
+/// One row per runner, with the race keys and every runner field, and the
+/// derived runner columns set in `options`.
+pub fn runner_batch(
+    races: &[RaceSummary],
+    options: &TableOptions,
+) -> Result<RecordBatch, ArrowError> {
+    record_batch(&runners_table(races, options))
+}
+
+/// One row per runner per section, with the derived section columns set in
+/// `options`.
+pub fn section_batch(
+    races: &[RaceSummary],
+    options: &TableOptions,
+) -> Result<RecordBatch, ArrowError> {
+    record_batch(&sections_table(races, options))
+}
+
+/// Every normalised table as a named record batch, without going through a
+/// file.
+pub fn record_batches(
+    races: &[RaceSummary],
+    options: &TableOptions,
+) -> Result<Vec<(String, RecordBatch)>, ArrowError> {
+    normalised_tables(races, options)
+        .into_iter()
+        .map(|table| Ok((table.name.clone(), record_batch(&table)?)))
+        .collect()
+}
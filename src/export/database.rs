@@ -0,0 +1,234 @@
+use super::table::{Column, Table};
+use super::tables::Series;
+use crate::fields::{FieldKind, FieldValue};
+use crate::structs::horsesummary::HorseSummary;
+use crate::structs::racesummary::RaceSummary;
+use chrono::{NaiveDate, NaiveTime};
+use sqlx::database::HasArguments;
+use sqlx::{Arguments, Database, Encode, Executor, IntoArguments, Type};
+
+/// A database the race rows are written to, and how it stores race times.
+pub trait Backend: Database {
+    type Time: for<'q> Encode<'q, Self> + Type<Self> + Send + 'static;
+
+    fn time(time: &NaiveTime) -> Self::Time;
+}
+
+/// The child rows of one race in the normalised SQLite and Postgres schemas,
+/// in insert order: runners, then each runner's sections, speeds and ranks,
+/// then the fastest sections. Columns are named as in the migrations and
+/// empty tables are left out. A runner's rows get tables of their own to keep
+/// each insert well under the backends' bind parameter limits.
+pub fn race_rows(race: &RaceSummary) -> Vec<Table> {
+    let mut tables = vec![runner_rows(race)];
+    for horse in &race.horses {
+        tables.push(section_rows(race, horse));
+        tables.push(series_rows(race, horse, Series::Speeds));
+        tables.push(series_rows(race, horse, Series::Ranks));
+    }
+    tables.push(fastest_section_rows(race));
+    tables.retain(|table| !table.rows.is_empty());
+    tables
+}
+
+/// Replaces the child rows of `race` with its `race_rows`, each table in one
+/// statement. Sections and series cascade from the runners, so only the
+/// runners and fastest sections are deleted first.
+pub async fn replace_race_rows<DB: Backend>(
+    conn: &mut DB::Connection,
+    race: &RaceSummary,
+) -> Result<(), sqlx::Error>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> Option<i64>: Encode<'q, DB> + Type<DB>,
+    for<'q> bool: Encode<'q, DB> + Type<DB>,
+    for<'q> i64: Encode<'q, DB> + Type<DB>,
+    for<'q> f64: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+    for<'q> NaiveDate: Encode<'q, DB> + Type<DB>,
+{
+    for table in ["runners", "fastest_sections"] {
+        let mut sql = format!("DELETE FROM {} WHERE race_code = ", table);
+        let mut arguments = <DB as HasArguments>::Arguments::default();
+        arguments.add(i64::from(race.race_code));
+        // Writing to a String cannot fail
+        arguments.format_placeholder(&mut sql).unwrap();
+        sqlx::query_with(&sql, arguments)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    for table in race_rows(race) {
+        let mut sql = format!(
+            "INSERT INTO {} ({}) VALUES ",
+            table.name,
+            table.headers().join(", ")
+        );
+        let mut arguments = <DB as HasArguments>::Arguments::default();
+        for (i, values) in table.rows.iter().enumerate() {
+            sql.push_str(if i == 0 { "(" } else { ", (" });
+            for (j, value) in values.iter().enumerate() {
+                if j > 0 {
+                    sql.push_str(", ");
+                }
+                match value {
+                    FieldValue::Null => arguments.add(None::<i64>),
+                    FieldValue::Bool(value) => arguments.add(*value),
+                    FieldValue::Int(value) => arguments.add(*value),
+                    FieldValue::Float(value) => arguments.add(*value),
+                    FieldValue::Text(value) => arguments.add(value.clone()),
+                    FieldValue::Date(value) => arguments.add(*value),
+                    FieldValue::Time(value) => arguments.add(DB::time(value)),
+                }
+                arguments.format_placeholder(&mut sql).unwrap();
+            }
+            sql.push(')');
+        }
+        sqlx::query_with(&sql, arguments)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+fn columns(columns: &[(&str, FieldKind)]) -> Vec<Column> {
+    columns
+        .iter()
+        .map(|(name, kind)| Column::new(name, *kind))
+        .collect()
+}
+
+fn runner_rows(race: &RaceSummary) -> Table {
+    let mut table = Table::new(
+        "runners",
+        columns(&[
+            ("race_code", FieldKind::Int),
+            ("horse_code", FieldKind::Int),
+            ("horse_name", FieldKind::Text),
+            ("bib", FieldKind::Int),
+            ("draw_number", FieldKind::Int),
+            ("distance_travelled", FieldKind::Int),
+            ("distance_difference", FieldKind::Int),
+            ("final_rank", FieldKind::Int),
+            ("time_official", FieldKind::Bool),
+            ("official_margin", FieldKind::Float),
+            ("fastest_section_time", FieldKind::Time),
+            ("fastest_section_index", FieldKind::Int),
+            ("top_speed", FieldKind::Float),
+            ("top_speed_index", FieldKind::Int),
+            ("finish_time", FieldKind::Time),
+            ("result_state", FieldKind::Text),
+            ("result_substate", FieldKind::Text),
+        ]),
+    );
+    for horse in &race.horses {
+        table.push(vec![
+            FieldValue::Int(race.race_code.into()),
+            FieldValue::Int(horse.code.into()),
+            FieldValue::Text(horse.name.clone()),
+            FieldValue::Int(horse.bib.into()),
+            FieldValue::Int(horse.draw_number.into()),
+            FieldValue::Int(horse.distance_travelled.into()),
+            FieldValue::Int(horse.distance_difference.into()),
+            FieldValue::Int(horse.final_rank.into()),
+            FieldValue::Bool(horse.time_official),
+            FieldValue::Float(horse.official_margin),
+            FieldValue::Time(horse.fastest_section_time),
+            FieldValue::Int(horse.fastest_section_index.into()),
+            FieldValue::Float(horse.top_speed),
+            FieldValue::Int(horse.top_speed_index.into()),
+            FieldValue::Time(horse.finish_time),
+            FieldValue::Text(horse.result_state.clone()),
+            FieldValue::Text(horse.result_substate.clone()),
+        ]);
+    }
+    table
+}
+
+fn section_rows(race: &RaceSummary, horse: &HorseSummary) -> Table {
+    let mut table = Table::new(
+        "sections",
+        columns(&[
+            ("race_code", FieldKind::Int),
+            ("horse_code", FieldKind::Int),
+            ("section_number", FieldKind::Int),
+            ("cumulated_distance", FieldKind::Int),
+            ("margin_decimal", FieldKind::Float),
+            ("real_distance", FieldKind::Float),
+            ("rank", FieldKind::Int),
+            ("intermediate_time", FieldKind::Time),
+            ("section_time", FieldKind::Time),
+            ("avg_speed", FieldKind::Float),
+            ("top_speed", FieldKind::Float),
+            ("avg_stride_freq", FieldKind::Float),
+            ("average_stride_length", FieldKind::Float),
+            ("avg_distance_rail", FieldKind::Float),
+        ]),
+    );
+    for (i, section) in horse.sections.iter().enumerate() {
+        table.push(vec![
+            FieldValue::Int(race.race_code.into()),
+            FieldValue::Int(horse.code.into()),
+            FieldValue::Int(i as i64 + 1),
+            FieldValue::Int(section.cumulated_distance.into()),
+            FieldValue::Float(section.margin_decimal),
+            FieldValue::Float(section.real_distance),
+            FieldValue::Int(section.rank.into()),
+            FieldValue::Time(section.intermediate_time),
+            FieldValue::Time(section.section_time),
+            FieldValue::Float(section.avg_speed),
+            FieldValue::Float(section.top_speed),
+            FieldValue::Float(section.avg_stride_freq),
+            FieldValue::Float(section.average_stride_length),
+            FieldValue::Float(section.avg_distance_rail),
+        ]);
+    }
+    table
+}
+
+fn series_rows(race: &RaceSummary, horse: &HorseSummary, series: Series) -> Table {
+    let mut table = Table::new(
+        series.name(),
+        columns(&[
+            ("race_code", FieldKind::Int),
+            ("horse_code", FieldKind::Int),
+            ("point", FieldKind::Int),
+            ("distance", FieldKind::Int),
+            (series.value_name(), series.kind()),
+        ]),
+    );
+    for (i, (distance, value)) in series.points(horse).iter().enumerate() {
+        table.push(vec![
+            FieldValue::Int(race.race_code.into()),
+            FieldValue::Int(horse.code.into()),
+            FieldValue::Int(i as i64),
+            FieldValue::Int((*distance).into()),
+            series.value(*value),
+        ]);
+    }
+    table
+}
+
+fn fastest_section_rows(race: &RaceSummary) -> Table {
+    let mut table = Table::new(
+        "fastest_sections",
+        columns(&[
+            ("race_code", FieldKind::Int),
+            ("section_number", FieldKind::Int),
+            ("cumulated_distance", FieldKind::Int),
+            ("intermediate_time", FieldKind::Time),
+            ("section_time", FieldKind::Time),
+        ]),
+    );
+    for (i, section) in race.fastest_sections.iter().enumerate() {
+        table.push(vec![
+            FieldValue::Int(race.race_code.into()),
+            FieldValue::Int(i as i64 + 1),
+            FieldValue::Int(section.cumulated_distance.into()),
+            FieldValue::Time(section.intermediate_time),
+            FieldValue::Time(section.section_time),
+        ]);
+    }
+    table
+}
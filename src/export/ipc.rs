@@ -0,0 +1,50 @@
+use super::batch::{record_batch, record_batches};
+use super::table::Table;
+use super::tables::TableOptions;
+use crate::structs::racesummary::RaceSummary;
+use arrow::ipc::writer::{FileWriter, StreamWriter};
+use arrow::record_batch::RecordBatch;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Writes one record batch as an Arrow IPC file, which is also Feather v2.
+pub fn write_ipc(batch: &RecordBatch, path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let out = BufWriter::new(File::create(path)?);
+    let mut writer = FileWriter::try_new(out, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Writes one table in the Arrow IPC streaming format, for piping into
+/// tools that read a stream from stdin.
+pub fn write_ipc_stream<W: Write>(
+    table: &Table,
+    out: W,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let batch = record_batch(table)?;
+    let mut writer = StreamWriter::try_new(out, &batch.schema())?;
+    writer.write(&batch)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Writes the races, runners, sections, fastest sections, speeds and ranks
+/// tables as `<name>.arrow` files in `dir`, returning the paths written.
+/// Unlike Parquet, race times keep their millisecond duration type.
+pub fn write_ipc_tables(
+    races: &[RaceSummary],
+    dir: &Path,
+    options: &TableOptions,
+) -> Result<Vec<PathBuf>, Box<dyn Error + Send + Sync>> {
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for (name, batch) in record_batches(races, options)? {
+        let path = dir.join(format!("{}.arrow", name));
+        write_ipc(&batch, &path)?;
+        written.push(path);
+    }
+    Ok(written)
+}
@@ -0,0 +1,73 @@
+use crate::structs::racesummary::RaceSummary;
+use serde_json::Value;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Writes one race per line, including every nested section, speed and rank.
+pub fn write_ndjson<W: Write>(
+    races: &[RaceSummary],
+    mut out: W,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    for race in races {
+        serde_json::to_writer(&mut out, race)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes one runner per line as a `horse` object with its sections, speeds
+/// and ranks, beside every race field including the fastest sections.
+pub fn write_ndjson_runners<W: Write>(
+    races: &[RaceSummary],
+    mut out: W,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    for race in races {
+        let mut record = serde_json::to_value(race)?;
+        let Some(fields) = record.as_object_mut() else {
+            continue;
+        };
+        fields.remove("horses");
+        for horse in &race.horses {
+            fields.insert("horse".to_string(), serde_json::to_value(horse)?);
+            serde_json::to_writer(&mut out, &Value::Object(fields.clone()))?;
+            out.write_all(b"\n")?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// File name of a race's JSON document, e.g. `2023-08-18_Eagle_Farm_R1_1001.json`.
+pub fn document_name(race: &RaceSummary) -> String {
+    let course: String = race
+        .course_name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    format!(
+        "{}_{}_R{}_{}.json",
+        race.event_date, course, race.race_number, race.race_code
+    )
+}
+
+/// Writes every race as its own pretty printed JSON document in `dir`,
+/// returning the paths written.
+pub fn write_json_documents(
+    races: &[RaceSummary],
+    dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn Error + Send + Sync>> {
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for race in races {
+        let path = dir.join(document_name(race));
+        let mut out = BufWriter::new(File::create(&path)?);
+        serde_json::to_writer_pretty(&mut out, race)?;
+        out.write_all(b"\n")?;
+        out.flush()?;
+        written.push(path);
+    }
+    Ok(written)
+}
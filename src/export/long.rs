@@ -0,0 +1,95 @@
+use super::schema::{RaceValues, FASTEST_FIELDS, SECTION_GROUND_FIELDS};
+use super::table::{Column, Table};
+use super::tables::TableOptions;
+use crate::fields::{FieldKind, FieldValue, Fields};
+use crate::structs::horsesummary::HorseSummary;
+use crate::structs::racesummary::RaceSummary;
+use crate::structs::sectionsummary::SectionSummary;
+
+// Race and runner fields repeated on every section row to key it
+pub const RACE_KEYS: [&str; 5] = [
+    "event_date",
+    "meeting_code",
+    "race_number",
+    "race_code",
+    "course_name",
+];
+pub const HORSE_KEYS: [(&str, &str); 4] = [
+    ("horse_code", "code"),
+    ("horse_name", "name"),
+    ("bib", "bib"),
+    ("final_rank", "final_rank"),
+];
+
+// Derived section fields added to the sections table by `options`
+fn derived_section_fields(options: &TableOptions) -> Vec<(&'static str, FieldKind)> {
+    let mut fields = Vec::new();
+    if options.fastest {
+        fields.extend(FASTEST_FIELDS);
+    }
+    if options.ground_loss {
+        fields.extend(SECTION_GROUND_FIELDS);
+    }
+    fields
+}
+
+/// One row per race, runner and section, carrying every section field, and
+/// as set in `options` the race's fastest time for the section and the
+/// runner's deficit to it, and the ground it lost in the section.
+pub fn sections_table(races: &[RaceSummary], options: &TableOptions) -> Table {
+    let mut columns = Vec::new();
+    for key in RACE_KEYS {
+        columns.push(Column::new(key, RaceSummary::field_kind(key).unwrap()));
+    }
+    for (header, field) in HORSE_KEYS {
+        columns.push(Column::new(
+            header,
+            HorseSummary::field_kind(field).unwrap(),
+        ));
+    }
+    columns.push(Column::new("section_number", FieldKind::Int));
+    columns.push(Column::new("distance_to_finish", FieldKind::Int));
+    for (field, kind) in SectionSummary::FIELDS {
+        columns.push(Column::new(field, *kind));
+    }
+    let derived = derived_section_fields(options);
+    for (field, kind) in &derived {
+        columns.push(Column::new(field, *kind));
+    }
+
+    let mut table = Table::new("sections", columns);
+    for race in races {
+        let distance = race.distance();
+        let values = RaceValues::new(race);
+        for horse in &race.horses {
+            let sections = horse.sections_from_finish(distance.unwrap_or(0));
+            for (i, (from_finish, section)) in sections.enumerate() {
+                // Unknown without the race distance
+                let from_finish = distance.map(|_| from_finish);
+                let mut row: Vec<FieldValue> = RACE_KEYS
+                    .iter()
+                    .map(|key| race.field(key).unwrap())
+                    .collect();
+                row.extend(
+                    HORSE_KEYS
+                        .iter()
+                        .map(|(_, field)| horse.field(field).unwrap()),
+                );
+                row.push((i + 1).into());
+                row.push(from_finish.into());
+                row.extend(
+                    SectionSummary::FIELDS
+                        .iter()
+                        .map(|(field, _)| section.field(field).unwrap()),
+                );
+                row.extend(
+                    derived
+                        .iter()
+                        .map(|(field, _)| values.section_value(horse, section, field).unwrap()),
+                );
+                table.push(row);
+            }
+        }
+    }
+    table
+}
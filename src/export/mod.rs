@@ -0,0 +1,14 @@
+pub mod batch;
+pub mod database;
+pub mod ipc;
+pub mod json;
+pub mod long;
+pub mod parquet;
+pub mod partition;
+pub mod schema;
+pub mod sqlite;
+pub mod table;
+pub mod tables;
+pub mod text;
+pub mod wide;
+pub mod xlsx;
@@ -0,0 +1,88 @@
+use super::batch::record_batch;
+use super::table::Table;
+use super::tables::{normalised_tables, TableOptions};
+use crate::structs::racesummary::RaceSummary;
+use ::parquet::arrow::ArrowWriter;
+use ::parquet::basic::{Compression, ZstdLevel};
+use ::parquet::file::properties::WriterProperties;
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use clap::ValueEnum;
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Parquet codec used for every column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ParquetCompression {
+    None,
+    Snappy,
+    Zstd,
+}
+
+impl ParquetCompression {
+    fn codec(self) -> Compression {
+        match self {
+            ParquetCompression::None => Compression::UNCOMPRESSED,
+            ParquetCompression::Snappy => Compression::SNAPPY,
+            ParquetCompression::Zstd => Compression::ZSTD(ZstdLevel::default()),
+        }
+    }
+}
+
+// Parquet has no duration type, so race times, which are elapsed times
+// well under a day, are stored with the millisecond TIME type
+fn without_durations(batch: &RecordBatch) -> Result<RecordBatch, ArrowError> {
+    let time = DataType::Time32(TimeUnit::Millisecond);
+    let mut fields = Vec::new();
+    let mut columns = Vec::new();
+    for (field, column) in batch.schema().fields().iter().zip(batch.columns()) {
+        if let DataType::Duration(_) = field.data_type() {
+            fields.push(Field::new(field.name(), time.clone(), field.is_nullable()));
+            let millis = cast(&cast(column, &DataType::Int64)?, &DataType::Int32)?;
+            columns.push(cast(&millis, &time)?);
+        } else {
+            fields.push(field.as_ref().clone());
+            columns.push(column.clone());
+        }
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+/// Writes one table to a Parquet file. Time columns are written as times of
+/// day in milliseconds.
+pub fn write_parquet(
+    table: &Table,
+    path: &Path,
+    compression: ParquetCompression,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let batch = without_durations(&record_batch(table)?)?;
+    let properties = WriterProperties::builder()
+        .set_compression(compression.codec())
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Writes the races, runners, sections, fastest sections, speeds and ranks
+/// tables as `<name>.parquet` files in `dir`, returning the paths written.
+pub fn write_parquet_tables(
+    races: &[RaceSummary],
+    dir: &Path,
+    compression: ParquetCompression,
+    options: &TableOptions,
+) -> Result<Vec<PathBuf>, Box<dyn Error + Send + Sync>> {
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for table in normalised_tables(races, options) {
+        let path = dir.join(format!("{}.parquet", table.name));
+        write_parquet(&table, &path, compression)?;
+        written.push(path);
+    }
+    Ok(written)
+}
@@ -0,0 +1,148 @@
+use crate::structs::racesummary::RaceSummary;
+use chrono::{Datelike, NaiveDate};
+use inflector::Inflector;
+use log::info;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::slice;
+
+// Each race's files are written here first and moved into place once complete
+const STAGING_DIR: &str = ".staging";
+
+/// Racing season of a date, e.g. `2023-24`. Seasons run from 1 August to
+/// 31 July.
+pub fn season(date: NaiveDate) -> String {
+    let start = if date.month() >= 8 {
+        date.year()
+    } else {
+        date.year() - 1
+    };
+    format!("{}-{:02}", start, (start + 1) % 100)
+}
+
+/// Relative directory of a race's partition,
+/// `season=2023-24/venue=eagle_farm/date=2024-01-13`.
+pub fn partition(race: &RaceSummary) -> PathBuf {
+    PathBuf::from(format!("season={}", season(race.event_date)))
+        .join(format!("venue={}", race.course_name.to_snake_case()))
+        .join(format!("date={}", race.event_date))
+}
+
+/// Writes every race of `races` into its partition under `dir`, calling
+/// `write` with the race on its own and a directory to fill. The files it
+/// writes are stored under the race code, `runners.parquet` of race 1007 as
+/// `runners-1007.parquet`, and replace that race's files from an earlier
+/// run. Other races already in the partition are kept, so a meeting can be
+/// written a few races at a time. Returns the partition directories written.
+pub fn write_partitioned<F>(
+    races: &[RaceSummary],
+    dir: &Path,
+    mut write: F,
+) -> Result<Vec<PathBuf>, Box<dyn Error + Send + Sync>>
+where
+    F: FnMut(&[RaceSummary], &Path) -> Result<(), Box<dyn Error + Send + Sync>>,
+{
+    let staging = dir.join(STAGING_DIR);
+    let mut written = Vec::new();
+    for race in races {
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        fs::create_dir_all(&staging)?;
+        write(slice::from_ref(race), &staging)?;
+
+        let target = dir.join(partition(race));
+        fs::create_dir_all(&target)?;
+        for entry in fs::read_dir(&staging)? {
+            let staged = entry?.path();
+            fs::rename(
+                &staged,
+                target.join(race_file_name(&staged, race.race_code)),
+            )?;
+        }
+        if !written.contains(&target) {
+            info!("Wrote {:?}", target);
+            written.push(target);
+        }
+    }
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    Ok(written)
+}
+
+// `runners.parquet` of race 1007 is stored as `runners-1007.parquet`
+fn race_file_name(path: &Path, race_code: i32) -> OsString {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("-{}", race_code));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn race(race_code: i32) -> RaceSummary {
+        let mut race = RaceSummary::new();
+        race.race_code = race_code;
+        race.course_name = "Eagle Farm".to_string();
+        race.event_date = NaiveDate::from_ymd_opt(2024, 1, 13).unwrap();
+        race
+    }
+
+    fn write_codes(races: &[RaceSummary], dir: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let codes: Vec<String> = races
+            .iter()
+            .map(|race| race.race_code.to_string())
+            .collect();
+        fs::write(dir.join("runners.csv"), codes.join(","))?;
+        Ok(())
+    }
+
+    #[test]
+    fn season_starts_in_august() {
+        assert_eq!(
+            season(NaiveDate::from_ymd_opt(2023, 8, 1).unwrap()),
+            "2023-24"
+        );
+        assert_eq!(
+            season(NaiveDate::from_ymd_opt(2024, 7, 31).unwrap()),
+            "2023-24"
+        );
+        assert_eq!(
+            season(NaiveDate::from_ymd_opt(1999, 12, 1).unwrap()),
+            "1999-00"
+        );
+    }
+
+    #[test]
+    fn later_runs_replace_only_their_races() {
+        let dir = tempfile::tempdir().unwrap();
+        let written = write_partitioned(&[race(1), race(2)], dir.path(), write_codes).unwrap();
+        let partition = dir
+            .path()
+            .join("season=2023-24/venue=eagle_farm/date=2024-01-13");
+        assert_eq!(written, [partition.clone()]);
+
+        fs::write(partition.join("runners-2.csv"), "stale").unwrap();
+        write_partitioned(&[race(2), race(3)], dir.path(), write_codes).unwrap();
+
+        let mut files: Vec<_> = fs::read_dir(&partition)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["runners-1.csv", "runners-2.csv", "runners-3.csv"]);
+        assert_eq!(
+            fs::read_to_string(partition.join("runners-2.csv")).unwrap(),
+            "2"
+        );
+        assert!(!dir.path().join(STAGING_DIR).exists());
+    }
+}
@@ -0,0 +1,558 @@
+use super::text::{TextOptions, DEFAULT_NULL};
+use crate::analysis::finishing::finishing_speed;
+use crate::analysis::ground::{ground_loss, section_ground_losses, GroundLoss, SectionGroundLoss};
+use crate::analysis::pace::PACE_FIELDS;
+use crate::analysis::stats::LATE_SPLITS;
+use crate::analysis::style::RunStyle;
+use crate::fields::{FieldKind, FieldValue, Fields};
+use crate::structs::horsesummary::HorseSummary;
+use crate::structs::racesummary::RaceSummary;
+use crate::structs::sectionsummary::SectionSummary;
+use chrono::format::{Item, StrftimeItems};
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Column spec reproducing the original 55 column `all_race_summaries.csv`.
+pub const DEFAULT_SCHEMA: &str = include_str!("../../schemas/default.toml");
+
+/// Which runner section a column reads from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionRef {
+    // Position in `horse.sections`, negative counts back from the last
+    Index(isize),
+    // The section starting this many metres before the finish (`L600`)
+    FromFinish(i32),
+}
+
+impl SectionRef {
+    pub fn parse(selector: &str) -> Option<Self> {
+        let selector = selector.trim();
+        match selector.strip_prefix(['L', 'l']) {
+            Some(metres) => metres.parse().ok().map(SectionRef::FromFinish),
+            None => selector.parse().ok().map(SectionRef::Index),
+        }
+    }
+}
+
+// Section fields written for every split by `Schema::with_splits`, as
+// (header suffix, field)
+pub const SPLIT_FIELDS: [(&str, &str); 9] = [
+    ("rank", "rank"),
+    ("section_time", "section_time"),
+    ("total_time", "intermediate_time"),
+    ("real_distance", "real_distance"),
+    ("avg_speed", "avg_speed"),
+    ("top_speed", "top_speed"),
+    ("avg_stride_freq", "avg_stride_freq"),
+    ("average_stride_length", "average_stride_length"),
+    ("avg_distance_rail", "avg_distance_rail"),
+];
+
+/// Section fields compared with the race's fastest time for the same
+/// section, usable wherever a `SectionSummary` field is.
+pub const FASTEST_FIELDS: [(&str, FieldKind); 2] = [
+    ("race_fastest_time", FieldKind::Time),
+    ("deficit", FieldKind::Float),
+];
+
+/// Section fields measuring the ground a runner lost to the shortest trip
+/// through the section, usable wherever a `SectionSummary` field is.
+pub const SECTION_GROUND_FIELDS: [(&str, FieldKind); 3] = [
+    ("ground_loss", FieldKind::Float),
+    ("ground_loss_time", FieldKind::Float),
+    ("wide", FieldKind::Float),
+];
+
+/// Runner fields measuring the ground it lost to the finisher with the
+/// shortest trip, usable wherever a `HorseSummary` field is.
+pub const GROUND_FIELDS: [(&str, FieldKind); 4] = [
+    ("ground_loss", FieldKind::Float),
+    ("ground_loss_lengths", FieldKind::Float),
+    ("ground_loss_time", FieldKind::Float),
+    ("ground_adjusted_finish_time", FieldKind::Time),
+];
+
+fn derived_kind(fields: &[(&str, FieldKind)], field: &str) -> Option<FieldKind> {
+    fields
+        .iter()
+        .find(|(name, _)| *name == field)
+        .map(|(_, kind)| *kind)
+}
+
+pub fn section_field_kind(field: &str) -> Option<FieldKind> {
+    SectionSummary::field_kind(field)
+        .or_else(|| derived_kind(&FASTEST_FIELDS, field))
+        .or_else(|| derived_kind(&SECTION_GROUND_FIELDS, field))
+}
+
+// Metres of a `finishing_speed_<metres>` runner field, and whether it is the
+// `finishing_speed_<metres>_interpolated` flag beside it
+fn finishing_speed_field(field: &str) -> Option<(i32, bool)> {
+    let rest = field.strip_prefix("finishing_speed_")?;
+    let (metres, interpolated) = match rest.strip_suffix("_interpolated") {
+        Some(metres) => (metres, true),
+        None => (rest, false),
+    };
+    let metres = metres.parse().ok().filter(|metres| *metres > 0)?;
+    Some((metres, interpolated))
+}
+
+/// The runner fields of the finishing speed over each of `LATE_SPLITS`, each
+/// followed by whether it was interpolated.
+pub fn finishing_speed_fields() -> Vec<String> {
+    LATE_SPLITS
+        .iter()
+        .flat_map(|metres| {
+            [
+                format!("finishing_speed_{}", metres),
+                format!("finishing_speed_{}_interpolated", metres),
+            ]
+        })
+        .collect()
+}
+
+pub fn horse_field_kind(field: &str) -> Option<FieldKind> {
+    match field {
+        "run_style" => Some(FieldKind::Text),
+        _ => HorseSummary::field_kind(field)
+            .or_else(|| derived_kind(&GROUND_FIELDS, field))
+            .or_else(|| {
+                finishing_speed_field(field).map(|(_, interpolated)| {
+                    if interpolated {
+                        FieldKind::Bool
+                    } else {
+                        FieldKind::Float
+                    }
+                })
+            }),
+    }
+}
+
+/// A race for reading the fields of its runners and sections, including the
+/// derived ones. The ground losses of the `GROUND_FIELDS` and
+/// `SECTION_GROUND_FIELDS` are worked out for the whole race once, on first
+/// use, however many runners and columns read them.
+pub struct RaceValues<'a> {
+    pub race: &'a RaceSummary,
+    // By horse code
+    ground: OnceCell<HashMap<i32, GroundLoss>>,
+    // By horse code and cumulated distance
+    section_ground: OnceCell<HashMap<(i32, i32), SectionGroundLoss>>,
+}
+
+impl<'a> RaceValues<'a> {
+    pub fn new(race: &'a RaceSummary) -> Self {
+        Self {
+            race,
+            ground: OnceCell::new(),
+            section_ground: OnceCell::new(),
+        }
+    }
+
+    fn ground(&self, horse: &HorseSummary) -> Option<GroundLoss> {
+        let race = self.race;
+        self.ground
+            .get_or_init(|| {
+                race.horses
+                    .iter()
+                    .filter_map(|horse| Some((horse.code, ground_loss(race, horse)?)))
+                    .collect()
+            })
+            .get(&horse.code)
+            .copied()
+    }
+
+    fn section_ground(
+        &self,
+        horse: &HorseSummary,
+        section: &SectionSummary,
+    ) -> Option<SectionGroundLoss> {
+        self.section_ground
+            .get_or_init(|| section_ground_losses(self.race))
+            .get(&(horse.code, section.cumulated_distance))
+            .copied()
+    }
+
+    /// A field of `horse`, its `run_style` in the race, its
+    /// `finishing_speed_<metres>`: the percentage of its whole race average
+    /// speed it ran over the last that many metres, with
+    /// `finishing_speed_<metres>_interpolated` telling whether the time at
+    /// the mark was interpolated, or its ground loss in metres, lengths and
+    /// seconds and its `ground_adjusted_finish_time`.
+    pub fn horse_value(&self, horse: &HorseSummary, field: &str) -> Option<FieldValue> {
+        let race = self.race;
+        let ground = || self.ground(horse);
+        let value = match field {
+            "run_style" => RunStyle::of(race, horse)
+                .map(|style| style.to_string())
+                .as_deref()
+                .into(),
+            "ground_loss" => ground().map(|loss| loss.metres).into(),
+            "ground_loss_lengths" => ground().map(|loss| loss.lengths()).into(),
+            "ground_loss_time" => ground().map(|loss| loss.seconds).into(),
+            "ground_adjusted_finish_time" => ground().map(|loss| loss.adjusted_finish_time).into(),
+            _ => match finishing_speed_field(field) {
+                Some((metres, interpolated)) => match finishing_speed(race, horse, metres) {
+                    Some(speed) if interpolated => FieldValue::Bool(speed.interpolated),
+                    Some(speed) => FieldValue::Float(speed.percent),
+                    None => FieldValue::Null,
+                },
+                None => return horse.field(field),
+            },
+        };
+        Some(value)
+    }
+
+    /// A field of `horse`'s `section`, of the race's fastest section ending
+    /// at the same distance: `race_fastest_time` and the runner's `deficit`
+    /// to it in seconds, or of its ground loss: `ground_loss` in metres,
+    /// `ground_loss_time` in seconds and how much `wide`r than the finisher
+    /// nearest the rail it raced.
+    pub fn section_value(
+        &self,
+        horse: &HorseSummary,
+        section: &SectionSummary,
+        field: &str,
+    ) -> Option<FieldValue> {
+        let fastest = || self.race.fastest_section(section.cumulated_distance);
+        let ground = || self.section_ground(horse, section);
+        match field {
+            "race_fastest_time" => Some(fastest().map(|f| f.section_time).into()),
+            "deficit" => Some(fastest().map(|f| section.deficit(f)).into()),
+            "ground_loss" => Some(ground().map(|loss| loss.metres).into()),
+            "ground_loss_time" => Some(ground().map(|loss| loss.seconds).into()),
+            "wide" => Some(ground().and_then(|loss| loss.wide).into()),
+            _ => section.field(field),
+        }
+    }
+}
+
+/// Where the value of a column comes from, parsed from strings such as
+/// `race.event_date`, `horse.name`, `section[-1].section_time` or
+/// `section[L600].section_time`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Race(String),
+    Horse(String),
+    Section(SectionRef, String),
+}
+
+impl Source {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let (scope, field) = source
+            .rsplit_once('.')
+            .ok_or_else(|| format!("{:?} is not of the form <scope>.<field>", source))?;
+        let field = field.to_string();
+
+        let parsed = match scope {
+            "race" => Source::Race(field),
+            "horse" => Source::Horse(field),
+            _ => {
+                let index = scope
+                    .strip_prefix("section[")
+                    .and_then(|rest| rest.strip_suffix(']'))
+                    .ok_or_else(|| format!("unknown scope {:?} in {:?}", scope, source))?;
+                let section = SectionRef::parse(index)
+                    .ok_or_else(|| format!("bad section selector {:?} in {:?}", index, source))?;
+                Source::Section(section, field)
+            }
+        };
+
+        if parsed.kind().is_none() {
+            return Err(format!("unknown field in {:?}", source));
+        }
+        Ok(parsed)
+    }
+
+    pub fn kind(&self) -> Option<FieldKind> {
+        match self {
+            Source::Race(field) => RaceSummary::field_kind(field),
+            Source::Horse(field) => horse_field_kind(field),
+            Source::Section(_, field) => section_field_kind(field),
+        }
+    }
+
+    pub fn value(&self, race: &RaceValues, horse: &HorseSummary) -> FieldValue {
+        let value = match self {
+            Source::Race(field) => race.race.field(field),
+            Source::Horse(field) => race.horse_value(horse, field),
+            Source::Section(section, field) => find_section(race.race, horse, *section)
+                .and_then(|section| race.section_value(horse, section, field)),
+        };
+        value.unwrap_or(FieldValue::Null)
+    }
+}
+
+fn find_section<'a>(
+    race: &RaceSummary,
+    horse: &'a HorseSummary,
+    section: SectionRef,
+) -> Option<&'a SectionSummary> {
+    match section {
+        SectionRef::Index(index) if index < 0 => horse
+            .sections
+            .len()
+            .checked_sub(index.unsigned_abs())
+            .and_then(|i| horse.sections.get(i)),
+        SectionRef::Index(index) => horse.sections.get(index as usize),
+        SectionRef::FromFinish(metres) => race
+            .distance()
+            .and_then(|distance| horse.section_from_finish(distance, metres)),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnSpec {
+    pub header: String,
+    pub source: String,
+    // chrono format string for dates and times
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    // Decimal places for floats, full precision when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decimals: Option<usize>,
+    // Written instead of a missing value, defaults to the schema's `null`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub null: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaSpec {
+    #[serde(default = "default_null")]
+    pub null: String,
+    pub columns: Vec<ColumnSpec>,
+}
+
+fn default_null() -> String {
+    DEFAULT_NULL.to_string()
+}
+
+/// A validated column spec, ready to turn runners into rows.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    pub spec: SchemaSpec,
+    sources: Vec<Source>,
+}
+
+impl Schema {
+    pub fn from_spec(spec: SchemaSpec) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let sources = spec
+            .columns
+            .iter()
+            .map(|column| {
+                Source::parse(&column.source)
+                    .map_err(|e| format!("column {:?}: {}", column.header, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for column in &spec.columns {
+            let invalid = column.format.as_deref().is_some_and(|format| {
+                StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+            });
+            if invalid {
+                return Err(format!("column {:?}: invalid format", column.header).into());
+            }
+        }
+        Ok(Self { spec, sources })
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Self::from_spec(toml::from_str(text)?)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Replaces the columns keyed by distance from the finish with one block
+    /// of `SPLIT_FIELDS` per split, headed `last_<metres>_<field>`. The block
+    /// goes where the first such column was, or at the end.
+    pub fn with_splits(self, splits: &[i32]) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let is_split =
+            |source: &Source| matches!(source, Source::Section(SectionRef::FromFinish(_), _));
+        let position = self
+            .sources
+            .iter()
+            .position(is_split)
+            .unwrap_or(self.sources.len());
+
+        let mut spec = self.spec;
+        let mut columns: Vec<ColumnSpec> = spec
+            .columns
+            .into_iter()
+            .zip(&self.sources)
+            .filter(|(_, source)| !is_split(source))
+            .map(|(column, _)| column)
+            .collect();
+
+        let block = splits.iter().flat_map(|metres| {
+            SPLIT_FIELDS.iter().map(move |(suffix, field)| ColumnSpec {
+                header: format!("last_{}_{}", metres, suffix),
+                source: format!("section[L{}].{}", metres, field),
+                format: None,
+                decimals: None,
+                null: None,
+            })
+        });
+        columns.splice(position..position, block);
+        spec.columns = columns;
+        Self::from_spec(spec)
+    }
+
+    /// Adds `last_<metres>_fastest_time` and `last_<metres>_deficit` after
+    /// the last column of every split, comparing the runner with the
+    /// fastest time for that section in the race.
+    pub fn with_fastest(self) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let split = |source: &Source| match source {
+            Source::Section(SectionRef::FromFinish(metres), _) => Some(*metres),
+            _ => None,
+        };
+        let mut spec = self.spec;
+        let mut columns = Vec::new();
+        for (i, column) in spec.columns.into_iter().enumerate() {
+            columns.push(column);
+            let Some(metres) = split(&self.sources[i]) else {
+                continue;
+            };
+            if self.sources[i + 1..]
+                .iter()
+                .any(|source| split(source) == Some(metres))
+            {
+                continue;
+            }
+            for (suffix, field) in [
+                ("fastest_time", "race_fastest_time"),
+                ("deficit", "deficit"),
+            ] {
+                columns.push(ColumnSpec {
+                    header: format!("last_{}_{}", metres, suffix),
+                    source: format!("section[L{}].{}", metres, field),
+                    format: None,
+                    decimals: None,
+                    null: None,
+                });
+            }
+        }
+        spec.columns = columns;
+        Self::from_spec(spec)
+    }
+
+    /// Adds `finishing_speed_600`, `_400` and `_200`, each followed by its
+    /// `_interpolated` flag, after the last runner column, or at the end.
+    pub fn with_finishing_speed(self) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        self.with_horse_fields(&finishing_speed_fields())
+    }
+
+    /// Adds `run_style` after the last runner column, or at the end.
+    pub fn with_run_style(self) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        self.with_horse_fields(&["run_style".to_string()])
+    }
+
+    /// Adds the `GROUND_FIELDS` after the last runner column, or at the end.
+    pub fn with_ground_loss(self) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let fields = GROUND_FIELDS.map(|(field, _)| field.to_string());
+        self.with_horse_fields(&fields)
+    }
+
+    /// Adds the `PACE_FIELDS` after the last race column, or at the end.
+    pub fn with_pace(self) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let fields = PACE_FIELDS.map(str::to_string);
+        let position = self
+            .sources
+            .iter()
+            .rposition(|source| matches!(source, Source::Race(_)))
+            .map_or(self.sources.len(), |i| i + 1);
+        self.with_fields_at(position, "race", &fields)
+    }
+
+    // Columns of runner fields, headed by the field, after the last runner
+    // column
+    fn with_horse_fields(self, fields: &[String]) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let position = self
+            .sources
+            .iter()
+            .rposition(|source| matches!(source, Source::Horse(_)))
+            .map_or(self.sources.len(), |i| i + 1);
+        self.with_fields_at(position, "horse", fields)
+    }
+
+    // Columns of `scope` fields, headed by the field, inserted at `position`
+    fn with_fields_at(
+        self,
+        position: usize,
+        scope: &str,
+        fields: &[String],
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut spec = self.spec;
+        let block = fields.iter().map(|field| ColumnSpec {
+            header: field.clone(),
+            source: format!("{}.{}", scope, field),
+            format: None,
+            decimals: None,
+            null: None,
+        });
+        spec.columns.splice(position..position, block);
+        Self::from_spec(spec)
+    }
+
+    pub fn headers(&self) -> Vec<&str> {
+        self.spec
+            .columns
+            .iter()
+            .map(|column| column.header.as_str())
+            .collect()
+    }
+
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    /// Typed values of every column for one runner.
+    pub fn values(&self, race: &RaceValues, horse: &HorseSummary) -> Vec<FieldValue> {
+        self.sources
+            .iter()
+            .map(|source| source.value(race, horse))
+            .collect()
+    }
+
+    /// Every column for one runner, formatted as text. A column's own
+    /// `format`, `decimals` and `null` win over `options`, whose `null` wins
+    /// over the schema's.
+    pub fn row(
+        &self,
+        race: &RaceValues,
+        horse: &HorseSummary,
+        options: &TextOptions,
+    ) -> Vec<String> {
+        self.values(race, horse)
+            .iter()
+            .zip(&self.spec.columns)
+            .map(|(value, column)| self.format(value, column, options))
+            .collect()
+    }
+
+    fn format(&self, value: &FieldValue, column: &ColumnSpec, options: &TextOptions) -> String {
+        match (value, &column.format, column.decimals) {
+            (FieldValue::Null, _, _) => column
+                .null
+                .as_ref()
+                .or(options.null.as_ref())
+                .unwrap_or(&self.spec.null)
+                .clone(),
+            (FieldValue::Float(value), _, Some(decimals)) => format!("{:.*}", decimals, value),
+            (FieldValue::Date(value), Some(format), _) => value.format(format).to_string(),
+            (FieldValue::Time(value), Some(format), _) => value.format(format).to_string(),
+            (value, _, _) => options.value(value, &self.spec.null),
+        }
+    }
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Self::from_toml(DEFAULT_SCHEMA).expect("bundled default schema is valid")
+    }
+}
@@ -0,0 +1,120 @@
+use super::database::{replace_race_rows, Backend};
+use crate::fields::seconds;
+use crate::report::RunReport;
+use crate::structs::racesummary::RaceSummary;
+use chrono::NaiveTime;
+use log::{error, info};
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqliteSynchronous};
+use sqlx::{Sqlite, Transaction};
+use std::error::Error;
+use std::path::Path;
+
+/// Schema migrations bundled into the binary from `migrations/sqlite`. The
+/// applied schema version is recorded in `_sqlx_migrations`.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+// Race times are stored in seconds
+impl Backend for Sqlite {
+    type Time = f64;
+
+    fn time(time: &NaiveTime) -> f64 {
+        seconds(time)
+    }
+}
+
+/// Opens or creates the database at `path` and applies pending migrations.
+pub async fn open(path: &Path) -> Result<SqlitePool, Box<dyn Error + Send + Sync>> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true)
+        .foreign_keys(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal);
+    let pool = SqlitePool::connect_with(options).await?;
+    MIGRATOR.run(&pool).await?;
+    Ok(pool)
+}
+
+/// Writes every race to the database at `path`, one transaction per race.
+/// Races already present are replaced, so re-importing is idempotent. A race
+/// that fails is rolled back, logged and recorded in `report`, and the rest
+/// are still written. Returns the number of races written.
+pub async fn write_sqlite(
+    races: &[RaceSummary],
+    path: &Path,
+    report: &mut RunReport,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let pool = open(path).await?;
+    let mut written = 0;
+    for race in races {
+        match write_race(&pool, race).await {
+            Ok(()) => written += 1,
+            Err(e) => {
+                error!("Failed to write race {}: {}", race.race_code, e);
+                report.add_failed_race(race, e.to_string());
+            }
+        }
+    }
+    info!("Wrote {} races to {:?}", written, path);
+    pool.close().await;
+    Ok(written)
+}
+
+async fn write_race(pool: &SqlitePool, race: &RaceSummary) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    insert_race(&mut tx, race).await?;
+    tx.commit().await
+}
+
+async fn insert_race(
+    tx: &mut Transaction<'_, Sqlite>,
+    race: &RaceSummary,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO races (race_code, event_date, meeting_code, race_number, event_name,
+                            course_name, race_name, distance, finish_time, track_name,
+                            track_condition, rail_position, early_split, late_split, pace,
+                            early_pace_index, late_pace_index)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT (race_code) DO UPDATE SET
+             event_date = excluded.event_date,
+             meeting_code = excluded.meeting_code,
+             race_number = excluded.race_number,
+             event_name = excluded.event_name,
+             course_name = excluded.course_name,
+             race_name = excluded.race_name,
+             distance = excluded.distance,
+             finish_time = excluded.finish_time,
+             track_name = excluded.track_name,
+             track_condition = excluded.track_condition,
+             rail_position = excluded.rail_position,
+             early_split = excluded.early_split,
+             late_split = excluded.late_split,
+             pace = excluded.pace,
+             early_pace_index = excluded.early_pace_index,
+             late_pace_index = excluded.late_pace_index",
+    )
+    .bind(race.race_code)
+    .bind(race.event_date)
+    .bind(race.meeting_code)
+    .bind(race.race_number)
+    .bind(&race.event_name)
+    .bind(&race.course_name)
+    .bind(&race.race_name)
+    .bind(race.distance())
+    .bind(seconds(&race.finish_time))
+    .bind(&race.track_name)
+    .bind(&race.track_condition)
+    .bind(&race.rail_position)
+    .bind(race.early_split.as_ref().map(seconds))
+    .bind(race.late_split.as_ref().map(seconds))
+    .bind(race.pace.map(|pace| pace.to_string()))
+    .bind(race.early_pace_index)
+    .bind(race.late_pace_index)
+    .execute(&mut **tx)
+    .await?;
+
+    replace_race_rows::<Sqlite>(tx, race).await?;
+    Ok(())
+}
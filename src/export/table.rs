@@ -0,0 +1,66 @@
+use super::text::{TextOptions, DEFAULT_NULL};
+use crate::fields::{FieldKind, FieldValue};
+use std::error::Error;
+use std::io::Write;
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: String,
+    pub kind: FieldKind,
+}
+
+impl Column {
+    pub fn new(name: &str, kind: FieldKind) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+        }
+    }
+}
+
+/// Rows of typed values under named, typed columns. Every export that is not
+/// a nested document is built as one or more tables.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub name: String,
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<FieldValue>>,
+}
+
+impl Table {
+    pub fn new(name: &str, columns: Vec<Column>) -> Self {
+        Self {
+            name: name.to_string(),
+            columns,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<FieldValue>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+
+    pub fn headers(&self) -> Vec<&str> {
+        self.columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect()
+    }
+}
+
+/// Writes a table as CSV, formatted by `options`.
+pub fn write_table_csv<W: Write>(
+    table: &Table,
+    options: &TextOptions,
+    out: W,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let null = options.null.as_deref().unwrap_or(DEFAULT_NULL);
+    let mut wtr = options.writer(out);
+    wtr.write_record(options.headers(table.headers()))?;
+    for row in &table.rows {
+        wtr.write_record(row.iter().map(|value| options.value(value, null)))?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
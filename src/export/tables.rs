@@ -0,0 +1,440 @@
+use super::long::{sections_table, HORSE_KEYS, RACE_KEYS};
+use super::schema::{finishing_speed_fields, horse_field_kind, RaceValues, GROUND_FIELDS};
+use super::table::{Column, Table};
+use crate::analysis::benchmark::{going, race_class, Benchmarks};
+use crate::analysis::stats::RaceStats;
+use crate::analysis::style::{RunStyle, StyleProfiles};
+use crate::fields::{FieldKind, FieldValue, Fields};
+use crate::structs::fssummary::FastestSectionSummary;
+use crate::structs::horsesummary::HorseSummary;
+use crate::structs::racesummary::RaceSummary;
+
+/// Options for the tables built from a set of races.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TableOptions {
+    // Resample the speed and rank series to every this many metres
+    pub grid: Option<i32>,
+    // Compare every runner section with the race's fastest section
+    pub fastest: bool,
+    // Add the runners' finishing speeds over the late splits
+    pub finishing_speed: bool,
+    // Add the runners' run styles
+    pub run_style: bool,
+    // Add the ground runners lost to the shortest trip
+    pub ground_loss: bool,
+}
+
+/// The races, runners, sections, fastest sections, speeds and ranks tables,
+/// as written by the Parquet and Arrow exports.
+pub fn normalised_tables(races: &[RaceSummary], options: &TableOptions) -> Vec<Table> {
+    vec![
+        races_table(races),
+        runners_table(races, options),
+        sections_table(races, options),
+        fastest_sections_table(races),
+        series_table(races, Series::Speeds, options.grid),
+        series_table(races, Series::Ranks, options.grid),
+    ]
+}
+
+/// One row per race with every race field, including the leader's splits
+/// and pace set by `analysis::pace::rate_pace`, and the race distance.
+pub fn races_table(races: &[RaceSummary]) -> Table {
+    let mut columns: Vec<Column> = RaceSummary::FIELDS
+        .iter()
+        .map(|(field, kind)| Column::new(field, *kind))
+        .collect();
+    columns.push(Column::new("distance", FieldKind::Int));
+
+    let mut table = Table::new("races", columns);
+    for race in races {
+        let mut row: Vec<FieldValue> = RaceSummary::FIELDS
+            .iter()
+            .map(|(field, _)| race.field(field).unwrap())
+            .collect();
+        row.push(race.distance().into());
+        table.push(row);
+    }
+    table
+}
+
+// Header of a runner field, named as in the keys of the other tables
+fn horse_header(field: &str) -> &str {
+    HORSE_KEYS
+        .iter()
+        .find(|(_, key)| *key == field)
+        .map_or(field, |(header, _)| header)
+}
+
+/// One row per race summarising it: conditions, field, winner, the leader's
+/// early and late splits and their pace, as set by
+/// `analysis::pace::rate_pace`.
+pub fn race_summary_table(races: &[RaceSummary]) -> Table {
+    let columns = vec![
+        Column::new("event_date", FieldKind::Date),
+        Column::new("venue", FieldKind::Text),
+        Column::new("race_number", FieldKind::Int),
+        Column::new("race_code", FieldKind::Int),
+        Column::new("race_name", FieldKind::Text),
+        Column::new("distance", FieldKind::Int),
+        Column::new("track_condition", FieldKind::Text),
+        Column::new("rail_position", FieldKind::Text),
+        Column::new("field_size", FieldKind::Int),
+        Column::new("finishers", FieldKind::Int),
+        Column::new("winner_code", FieldKind::Int),
+        Column::new("winner_name", FieldKind::Text),
+        Column::new("winning_time", FieldKind::Time),
+        Column::new("early_split", FieldKind::Time),
+        Column::new("late_split", FieldKind::Time),
+        Column::new("pace", FieldKind::Text),
+        Column::new("early_pace_index", FieldKind::Float),
+        Column::new("late_pace_index", FieldKind::Float),
+    ];
+
+    let mut table = Table::new("race_summary", columns);
+    for race in races {
+        let stats = RaceStats::new(race);
+        table.push(vec![
+            race.event_date.into(),
+            race.course_name.as_str().into(),
+            race.race_number.into(),
+            race.race_code.into(),
+            race.race_name.as_str().into(),
+            stats.distance.into(),
+            race.track_condition.as_str().into(),
+            race.rail_position.as_str().into(),
+            stats.field_size.into(),
+            stats.finishers.into(),
+            stats.winner.map(|horse| horse.code).into(),
+            stats.winner.map(|horse| horse.name.as_str()).into(),
+            stats.winning_time.into(),
+            race.early_split.into(),
+            race.late_split.into(),
+            race.pace.map(|pace| pace.to_string()).as_deref().into(),
+            race.early_pace_index.into(),
+            race.late_pace_index.into(),
+        ]);
+    }
+    table
+}
+
+// Derived runner fields added to the runners table by `options`
+fn derived_horse_fields(options: &TableOptions) -> Vec<String> {
+    let mut fields = Vec::new();
+    if options.finishing_speed {
+        fields.extend(finishing_speed_fields());
+    }
+    if options.run_style {
+        fields.push("run_style".to_string());
+    }
+    if options.ground_loss {
+        fields.extend(GROUND_FIELDS.map(|(field, _)| field.to_string()));
+    }
+    fields
+}
+
+/// One row per runner with the race keys and every runner field, and as set
+/// in `options` its finishing speeds over the late splits, its run
+/// style and its ground loss.
+pub fn runners_table(races: &[RaceSummary], options: &TableOptions) -> Table {
+    let mut columns: Vec<Column> = RACE_KEYS
+        .iter()
+        .map(|key| Column::new(key, RaceSummary::field_kind(key).unwrap()))
+        .collect();
+    for (field, kind) in HorseSummary::FIELDS {
+        columns.push(Column::new(horse_header(field), *kind));
+    }
+    let derived = derived_horse_fields(options);
+    for field in &derived {
+        columns.push(Column::new(field, horse_field_kind(field).unwrap()));
+    }
+
+    let mut table = Table::new("runners", columns);
+    for race in races {
+        let values = RaceValues::new(race);
+        for horse in &race.horses {
+            let mut row = race_keys(race);
+            row.extend(
+                HorseSummary::FIELDS
+                    .iter()
+                    .map(|(field, _)| horse.field(field).unwrap()),
+            );
+            row.extend(
+                derived
+                    .iter()
+                    .map(|field| values.horse_value(horse, field).unwrap()),
+            );
+            table.push(row);
+        }
+    }
+    table
+}
+
+/// One row per race and fastest section: the best time any runner recorded
+/// for each section of the race.
+pub fn fastest_sections_table(races: &[RaceSummary]) -> Table {
+    let mut columns: Vec<Column> = RACE_KEYS
+        .iter()
+        .map(|key| Column::new(key, RaceSummary::field_kind(key).unwrap()))
+        .collect();
+    columns.push(Column::new("section_number", FieldKind::Int));
+    columns.push(Column::new("distance_to_finish", FieldKind::Int));
+    for (field, kind) in FastestSectionSummary::FIELDS {
+        columns.push(Column::new(field, *kind));
+    }
+
+    let mut table = Table::new("fastest_sections", columns);
+    for race in races {
+        let distance = race.distance();
+        let mut start = 0;
+        for (i, section) in race.fastest_sections.iter().enumerate() {
+            let mut row = race_keys(race);
+            row.push((i + 1).into());
+            row.push(distance.map(|distance| distance - start).into());
+            row.extend(
+                FastestSectionSummary::FIELDS
+                    .iter()
+                    .map(|(field, _)| section.field(field).unwrap()),
+            );
+            table.push(row);
+            start = section.cumulated_distance;
+        }
+    }
+    table
+}
+
+/// A tracking series recorded for each runner as (metres run, value) pairs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Series {
+    Speeds,
+    Ranks,
+}
+
+impl Series {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Series::Speeds => "speeds",
+            Series::Ranks => "ranks",
+        }
+    }
+
+    pub fn points<'a>(&self, horse: &'a HorseSummary) -> &'a [(i32, f64)] {
+        match self {
+            Series::Speeds => &horse.speeds,
+            Series::Ranks => &horse.ranks,
+        }
+    }
+
+    /// Header of the value column.
+    pub fn value_name(&self) -> &'static str {
+        match self {
+            Series::Speeds => "speed",
+            Series::Ranks => "rank",
+        }
+    }
+
+    // Ranks are whole places, speeds are km/h
+    pub(crate) fn kind(&self) -> FieldKind {
+        match self {
+            Series::Speeds => FieldKind::Float,
+            Series::Ranks => FieldKind::Int,
+        }
+    }
+
+    pub(crate) fn value(&self, value: f64) -> FieldValue {
+        match self {
+            Series::Speeds => value.into(),
+            Series::Ranks => FieldValue::Int(value.round() as i64),
+        }
+    }
+
+    /// Values at every multiple of `step` metres between the first and last
+    /// recorded points. Speeds are interpolated linearly, ranks hold the
+    /// last recorded place. A `step` that is not positive gives no points.
+    pub fn resample(&self, points: &[(i32, f64)], step: i32) -> Vec<(i32, f64)> {
+        if step <= 0 {
+            return Vec::new();
+        }
+        let mut points = points.to_vec();
+        points.sort_by_key(|(distance, _)| *distance);
+        let (Some(first), Some(last)) = (points.first(), points.last()) else {
+            return Vec::new();
+        };
+
+        let mut start = first.0.div_euclid(step) * step;
+        if start < first.0 {
+            start += step;
+        }
+        let mut resampled = Vec::new();
+        let mut i = 0;
+        for distance in (start..=last.0).step_by(step as usize) {
+            while i + 1 < points.len() && points[i + 1].0 <= distance {
+                i += 1;
+            }
+            let (from, value) = points[i];
+            let value = match (self, points.get(i + 1)) {
+                (Series::Speeds, Some(&(to, next))) if from < distance => {
+                    value + (next - value) * (distance - from) as f64 / (to - from) as f64
+                }
+                _ => value,
+            };
+            resampled.push((distance, value));
+        }
+        resampled
+    }
+}
+
+/// One row per runner and point of `series`, keyed by race and horse, or
+/// per `grid` metres when given.
+pub fn series_table(races: &[RaceSummary], series: Series, grid: Option<i32>) -> Table {
+    let mut columns = series_key_columns();
+    columns.push(Column::new("distance", FieldKind::Int));
+    columns.push(Column::new(series.value_name(), series.kind()));
+
+    let mut table = Table::new(series.name(), columns);
+    for race in races {
+        for horse in &race.horses {
+            let points = match grid {
+                Some(step) => series.resample(series.points(horse), step),
+                None => series.points(horse).to_vec(),
+            };
+            for (distance, value) in points {
+                let mut row = series_keys(race, horse);
+                row.push(distance.into());
+                row.push(series.value(value));
+                table.push(row);
+            }
+        }
+    }
+    table
+}
+
+/// One row per runner and benchmarked time (finish, to the 600 m mark, last
+/// 600/400/200 m and every section): the time in seconds, the par (median)
+/// for the race's venue, distance, going and class, the deviation from it
+/// and the estimated percentile, lower being faster.
+pub fn par_table(races: &[RaceSummary], benchmarks: &Benchmarks) -> Table {
+    let mut columns = series_key_columns();
+    for (name, kind) in [
+        ("going", FieldKind::Text),
+        ("class", FieldKind::Text),
+        ("metric", FieldKind::Text),
+        ("time", FieldKind::Float),
+        ("par", FieldKind::Float),
+        ("deviation", FieldKind::Float),
+        ("percentile", FieldKind::Float),
+        ("benchmark_count", FieldKind::Int),
+    ] {
+        columns.push(Column::new(name, kind));
+    }
+
+    let mut table = Table::new("par", columns);
+    for race in races {
+        for horse in &race.horses {
+            for deviation in benchmarks.deviations(race, horse) {
+                let mut row = series_keys(race, horse);
+                row.extend([
+                    going(&race.track_condition).as_str().into(),
+                    race_class(&race.race_name).as_str().into(),
+                    deviation.metric.to_string().as_str().into(),
+                    deviation.time.into(),
+                    deviation.benchmark.median.into(),
+                    deviation.deviation().into(),
+                    deviation.percentile().into(),
+                    deviation.benchmark.count.into(),
+                ]);
+                table.push(row);
+            }
+        }
+    }
+    table
+}
+
+fn series_key_columns() -> Vec<Column> {
+    let mut columns: Vec<Column> = RACE_KEYS
+        .iter()
+        .map(|key| Column::new(key, RaceSummary::field_kind(key).unwrap()))
+        .collect();
+    for (header, field) in HORSE_KEYS {
+        columns.push(Column::new(
+            header,
+            HorseSummary::field_kind(field).unwrap(),
+        ));
+    }
+    columns
+}
+
+fn race_keys(race: &RaceSummary) -> Vec<FieldValue> {
+    RACE_KEYS
+        .iter()
+        .map(|key| race.field(key).unwrap())
+        .collect()
+}
+
+fn series_keys(race: &RaceSummary, horse: &HorseSummary) -> Vec<FieldValue> {
+    let mut row = race_keys(race);
+    row.extend(
+        HORSE_KEYS
+            .iter()
+            .map(|(_, field)| horse.field(field).unwrap()),
+    );
+    row
+}
+
+/// One row per horse with the number of runs in each style, its usual style
+/// and the share of its runs in it. Only the horses in `codes`, unless empty.
+pub fn styles_table(profiles: &StyleProfiles, codes: &[i32]) -> Table {
+    let mut columns = vec![
+        Column::new("horse_code", FieldKind::Int),
+        Column::new("horse_name", FieldKind::Text),
+        Column::new("runs", FieldKind::Int),
+    ];
+    for style in RunStyle::ALL {
+        columns.push(Column::new(&style.to_string(), FieldKind::Int));
+    }
+    columns.push(Column::new("usual_style", FieldKind::Text));
+    columns.push(Column::new("usual_share", FieldKind::Float));
+
+    let mut table = Table::new("styles", columns);
+    let selected = profiles
+        .iter()
+        .filter(|profile| codes.is_empty() || codes.contains(&profile.code));
+    for profile in selected {
+        let usual = profile.usual();
+        let mut row: Vec<FieldValue> = vec![
+            profile.code.into(),
+            profile.name.as_str().into(),
+            profile.runs().into(),
+        ];
+        row.extend(RunStyle::ALL.map(|style| profile.count(style).into()));
+        row.push(usual.map(|style| style.to_string()).as_deref().into());
+        row.push(usual.map(|style| profile.share(style)).into());
+        table.push(row);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resample_interpolates_speeds_and_holds_ranks() {
+        let points = [(5, 10.0), (25, 20.0)];
+        assert_eq!(
+            Series::Speeds.resample(&points, 10),
+            [(10, 12.5), (20, 17.5)]
+        );
+        assert_eq!(
+            Series::Ranks.resample(&points, 10),
+            [(10, 10.0), (20, 10.0)]
+        );
+    }
+
+    #[test]
+    fn resample_without_points_or_step_is_empty() {
+        assert!(Series::Speeds.resample(&[], 10).is_empty());
+        assert!(Series::Speeds.resample(&[(0, 1.0)], 0).is_empty());
+        assert!(Series::Ranks.resample(&[(0, 1.0)], -5).is_empty());
+    }
+}
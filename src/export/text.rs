@@ -0,0 +1,164 @@
+use crate::fields::{seconds, FieldKind, FieldValue};
+use chrono::NaiveTime;
+use clap::ValueEnum;
+use csv::{QuoteStyle, Writer, WriterBuilder};
+use inflector::Inflector;
+use std::io::Write;
+
+/// Written for a missing value when neither the options nor the schema say
+/// otherwise.
+pub const DEFAULT_NULL: &str = "N/A";
+
+/// How race times are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TimeFormat {
+    /// Elapsed seconds, e.g. `71.23`
+    Seconds,
+    /// `mm:ss.fff`, minutes running past 59 for long races
+    #[value(name = "mm:ss.fff")]
+    Minutes,
+    /// `hh:mm:ss.fff`
+    #[value(name = "hh:mm:ss.fff")]
+    Hours,
+}
+
+/// When fields are quoted, as in `csv::QuoteStyle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Quoting {
+    Necessary,
+    Always,
+    NonNumeric,
+    Never,
+}
+
+impl Quoting {
+    fn style(self) -> QuoteStyle {
+        match self {
+            Quoting::Necessary => QuoteStyle::Necessary,
+            Quoting::Always => QuoteStyle::Always,
+            Quoting::NonNumeric => QuoteStyle::NonNumeric,
+            Quoting::Never => QuoteStyle::Never,
+        }
+    }
+}
+
+/// Case of header names, which are snake_case as written by the schema and
+/// tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HeaderCase {
+    Snake,
+    Camel,
+    Pascal,
+    Kebab,
+    Title,
+    ScreamingSnake,
+}
+
+/// Formatting shared by every delimited text export. The defaults write
+/// what the CSV output always has: times as `NaiveTime` displays them,
+/// floats at full precision, comma delimited and quoted where necessary.
+#[derive(Debug, Clone)]
+pub struct TextOptions {
+    pub time: Option<TimeFormat>,
+    /// Written for missing values, the schema's `null` when unset
+    pub null: Option<String>,
+    pub delimiter: u8,
+    pub quoting: Quoting,
+    /// Decimal places by field kind, for floats and times
+    pub decimals: Vec<(FieldKind, usize)>,
+    pub header_case: Option<HeaderCase>,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            time: None,
+            null: None,
+            delimiter: b',',
+            quoting: Quoting::Necessary,
+            decimals: Vec::new(),
+            header_case: None,
+        }
+    }
+}
+
+impl TextOptions {
+    pub fn writer<W: Write>(&self, out: W) -> Writer<W> {
+        WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .quote_style(self.quoting.style())
+            .from_writer(out)
+    }
+
+    pub fn decimals(&self, kind: FieldKind) -> Option<usize> {
+        self.decimals
+            .iter()
+            .rev()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, decimals)| *decimals)
+    }
+
+    pub fn header(&self, name: &str) -> String {
+        match self.header_case {
+            None => name.to_string(),
+            Some(HeaderCase::Snake) => name.to_snake_case(),
+            Some(HeaderCase::Camel) => name.to_camel_case(),
+            Some(HeaderCase::Pascal) => name.to_pascal_case(),
+            Some(HeaderCase::Kebab) => name.to_kebab_case(),
+            Some(HeaderCase::Title) => name.to_title_case(),
+            Some(HeaderCase::ScreamingSnake) => name.to_screaming_snake_case(),
+        }
+    }
+
+    pub fn headers<'a>(&self, names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        names.into_iter().map(|name| self.header(name)).collect()
+    }
+
+    /// A value as text, with `null` for a missing value.
+    pub fn value(&self, value: &FieldValue, null: &str) -> String {
+        match value {
+            FieldValue::Null => null.to_string(),
+            FieldValue::Float(value) => match self.decimals(FieldKind::Float) {
+                Some(decimals) => format!("{:.*}", decimals, value),
+                None => value.to_string(),
+            },
+            FieldValue::Time(value) => self.time(value),
+            value => value.to_string(),
+        }
+    }
+
+    pub fn time(&self, time: &NaiveTime) -> String {
+        let decimals = self.decimals(FieldKind::Time);
+        let format = match (self.time, decimals) {
+            (None, None) => return time.to_string(),
+            (format, _) => format.unwrap_or(TimeFormat::Hours),
+        };
+        let elapsed = seconds(time);
+        if format == TimeFormat::Seconds {
+            return match decimals {
+                Some(decimals) => format!("{:.*}", decimals, elapsed),
+                None => elapsed.to_string(),
+            };
+        }
+
+        // Rounded once in whole units of the last decimal place, so that
+        // 59.9996 becomes 01:00.000 rather than 00:60.000
+        let decimals = decimals.unwrap_or(3);
+        let scale = 10_i64.pow(decimals as u32);
+        let units = (elapsed * scale as f64).round() as i64;
+        let (whole, fraction) = (units / scale, units % scale);
+        let clock = match format {
+            TimeFormat::Hours => format!(
+                "{:02}:{:02}:{:02}",
+                whole / 3600,
+                whole / 60 % 60,
+                whole % 60
+            ),
+            _ => format!("{:02}:{:02}", whole / 60, whole % 60),
+        };
+        match decimals {
+            0 => clock,
+            _ => format!("{}.{:0width$}", clock, fraction, width = decimals),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use super::schema::{RaceValues, Schema};
+use super::text::TextOptions;
+use crate::structs::racesummary::RaceSummary;
+use std::error::Error;
+use std::io::Write;
+
+/// Writes one row per runner with the columns of `schema`, formatted by
+/// `options` where the schema's columns leave it open.
+pub fn write_wide_csv<W: Write>(
+    races: &[RaceSummary],
+    schema: &Schema,
+    options: &TextOptions,
+    out: W,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut wtr = options.writer(out);
+    wtr.write_record(options.headers(schema.headers()))?;
+
+    for race in races {
+        let values = RaceValues::new(race);
+        for horse in &race.horses {
+            wtr.write_record(schema.row(&values, horse, options))?;
+        }
+    }
+
+    wtr.flush()?;
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use super::schema::{RaceValues, Schema, Source};
+use crate::fields::{seconds, FieldValue};
+use crate::structs::racesummary::RaceSummary;
+use chrono::{Datelike, NaiveTime};
+use clap::ValueEnum;
+use rust_xlsxwriter::{
+    ConditionalFormatTop, ConditionalFormatTopRule, ExcelDateTime, Format, Workbook, Worksheet,
+    XlsxError,
+};
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+
+/// How runners are split across the sheets of a workbook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SheetBy {
+    Meeting,
+    Race,
+}
+
+// Excel limits sheet names to 31 characters and forbids a few
+const SHEET_NAME_LEN: usize = 31;
+
+fn sheet_name(race: &RaceSummary, by: SheetBy, used: &mut HashSet<String>) -> String {
+    let name = match by {
+        SheetBy::Meeting => format!("{} {}", race.event_date, race.course_name),
+        SheetBy::Race => format!(
+            "{} {} R{}",
+            race.event_date, race.course_name, race.race_number
+        ),
+    };
+    let name: String = name
+        .chars()
+        .map(|c| if "[]:*?/\\".contains(c) { '_' } else { c })
+        .take(SHEET_NAME_LEN)
+        .collect();
+
+    let mut unique = name.clone();
+    let mut n = 2;
+    while !used.insert(unique.to_lowercase()) {
+        let suffix = format!(" ({})", n);
+        let stem: String = name.chars().take(SHEET_NAME_LEN - suffix.len()).collect();
+        unique = format!("{}{}", stem, suffix);
+        n += 1;
+    }
+    unique
+}
+
+struct Formats {
+    header: Format,
+    date: Format,
+    time: Format,
+    fastest: Format,
+}
+
+fn write_cell(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: &FieldValue,
+    formats: &Formats,
+) -> Result<(), XlsxError> {
+    match value {
+        FieldValue::Null => {}
+        FieldValue::Bool(value) => {
+            sheet.write_boolean(row, col, *value)?;
+        }
+        FieldValue::Int(value) => {
+            sheet.write_number(row, col, *value as f64)?;
+        }
+        FieldValue::Float(value) => {
+            sheet.write_number(row, col, *value)?;
+        }
+        FieldValue::Text(value) => {
+            sheet.write_string(row, col, value)?;
+        }
+        FieldValue::Date(value) => {
+            let date = ExcelDateTime::from_ymd(
+                value.year() as u16,
+                value.month() as u8,
+                value.day() as u8,
+            )?;
+            sheet.write_datetime_with_format(row, col, &date, &formats.date)?;
+        }
+        // Race times as a fraction of a day, the way Excel stores durations
+        FieldValue::Time(value) => {
+            sheet.write_number_with_format(row, col, seconds(value) / 86400.0, &formats.time)?;
+        }
+    }
+    Ok(())
+}
+
+// Columns of section times, in which the fastest time is highlighted
+fn is_section_time(source: &Source) -> bool {
+    matches!(source, Source::Section(_, field) if field == "section_time")
+}
+
+fn write_sheet(
+    sheet: &mut Worksheet,
+    races: &[&RaceSummary],
+    schema: &Schema,
+    formats: &Formats,
+) -> Result<(), XlsxError> {
+    for (col, header) in schema.headers().iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *header, &formats.header)?;
+    }
+
+    let sources = schema.sources();
+    let mut row = 0;
+    for race in races {
+        let values = RaceValues::new(race);
+        for horse in &race.horses {
+            row += 1;
+            for (col, value) in schema.values(&values, horse).iter().enumerate() {
+                // A section the runner has no time for reads 00:00:00, which
+                // would otherwise be highlighted as the fastest
+                if is_section_time(&sources[col]) && *value == FieldValue::Time(NaiveTime::MIN) {
+                    continue;
+                }
+                write_cell(sheet, row, col as u16, value, formats)?;
+            }
+        }
+    }
+
+    let last_col = schema.headers().len().saturating_sub(1) as u16;
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.autofilter(0, 0, row.max(1), last_col)?;
+    if row > 0 {
+        let fastest = ConditionalFormatTop::new()
+            .set_rule(ConditionalFormatTopRule::Bottom(1))
+            .set_format(&formats.fastest);
+        for (col, source) in sources.iter().enumerate() {
+            if is_section_time(source) {
+                sheet.add_conditional_format(1, col as u16, row, col as u16, &fastest)?;
+            }
+        }
+    }
+    sheet.autofit();
+    Ok(())
+}
+
+/// Writes one sheet per meeting or per race with a row per runner and the
+/// columns of `schema`. Cells are typed, the header row is frozen and
+/// filterable, and the fastest time of every section time column is
+/// highlighted.
+pub fn write_xlsx(
+    races: &[RaceSummary],
+    schema: &Schema,
+    by: SheetBy,
+    path: &Path,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let formats = Formats {
+        header: Format::new().set_bold(),
+        date: Format::new().set_num_format("yyyy-mm-dd"),
+        time: Format::new().set_num_format("mm:ss.000"),
+        fastest: Format::new()
+            .set_font_color("006100")
+            .set_background_color("C6EFCE"),
+    };
+
+    // Races arrive sorted, so each sheet's races are consecutive
+    let mut groups: Vec<Vec<&RaceSummary>> = Vec::new();
+    for race in races {
+        let same_sheet = groups
+            .last()
+            .and_then(|group| group.last())
+            .is_some_and(|last| {
+                by == SheetBy::Meeting
+                    && last.meeting_code == race.meeting_code
+                    && last.event_date == race.event_date
+            });
+        match groups.last_mut() {
+            Some(group) if same_sheet => group.push(race),
+            _ => groups.push(vec![race]),
+        }
+    }
+
+    let mut workbook = Workbook::new();
+    let mut used = HashSet::new();
+    for group in &groups {
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(sheet_name(group[0], by, &mut used))?;
+        write_sheet(sheet, group, schema, &formats)?;
+    }
+    if groups.is_empty() {
+        write_sheet(workbook.add_worksheet(), &[], schema, &formats)?;
+    }
+    workbook.save(path)?;
+    Ok(())
+}
@@ -0,0 +1,330 @@
+use crate::{files_under, unused_path};
+use clap::ValueEnum;
+use log::{debug, info, warn};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use zip::read::ZipFile;
+
+/// Bounds applied to every archive before and while it is extracted. The
+/// sizes recorded in an archive are checked up front but not trusted: the
+/// limits are enforced again on the bytes actually decompressed.
+#[derive(Debug, Clone, Copy)]
+pub struct ZipLimits {
+    pub max_total_size: u64,
+    pub max_entry_size: u64,
+    pub max_entries: usize,
+    // Largest allowed uncompressed / compressed size of a single entry
+    pub max_ratio: f64,
+    // Uncompressed size an entry may reach whatever its ratio, so that small
+    // but highly compressible files are not taken for bombs
+    pub ratio_min_size: u64,
+}
+
+impl Default for ZipLimits {
+    fn default() -> Self {
+        Self {
+            max_total_size: 1024 * 1024 * 1024,
+            max_entry_size: 256 * 1024 * 1024,
+            max_entries: 10_000,
+            max_ratio: 100.0,
+            ratio_min_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// What to do when an entry would land on a file that already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    Skip,
+    Overwrite,
+    KeepBoth,
+    // Skip identical content, keep both copies when the content differs
+    CompareHash,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    pub data_dir: PathBuf,
+    pub output_dir: PathBuf,
+    pub limits: ZipLimits,
+    pub apply_permissions: bool,
+    pub collisions: CollisionPolicy,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("./data"),
+            output_dir: PathBuf::from("./unzipped_sectionals"),
+            limits: ZipLimits::default(),
+            apply_permissions: false,
+            collisions: CollisionPolicy::CompareHash,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Extracted,
+    Overwritten,
+    KeptBoth,
+    SkippedExisting,
+    SkippedIdentical,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractEvent {
+    pub archive: PathBuf,
+    pub entry: Option<String>,
+    pub target: Option<PathBuf>,
+    pub action: Action,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ExtractLog {
+    pub events: Vec<ExtractEvent>,
+}
+
+impl ExtractLog {
+    fn push(
+        &mut self,
+        archive: &Path,
+        entry: Option<&str>,
+        target: Option<&Path>,
+        action: Action,
+        reason: Option<String>,
+    ) {
+        match &reason {
+            Some(reason) => warn!("{:?} {:?}: {:?} ({})", archive, entry, action, reason),
+            None => info!("{:?} {:?} -> {:?}: {:?}", archive, entry, target, action),
+        }
+        self.events.push(ExtractEvent {
+            archive: archive.to_path_buf(),
+            entry: entry.map(str::to_string),
+            target: target.map(Path::to_path_buf),
+            action,
+            reason,
+        });
+    }
+
+    pub fn count(&self, action: Action) -> usize {
+        self.events.iter().filter(|e| e.action == action).count()
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+fn limit_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Raised once the archive as a whole is too large, which ends its extraction
+fn total_limit_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::FileTooLarge, message)
+}
+
+/// Checks the entry count of an archive against the limits.
+pub fn check_entry_count<R: Read + io::Seek>(
+    archive: &zip::ZipArchive<R>,
+    limits: &ZipLimits,
+) -> io::Result<()> {
+    if archive.len() > limits.max_entries {
+        return Err(total_limit_error(format!(
+            "{} entries exceeds the limit of {}",
+            archive.len(),
+            limits.max_entries
+        )));
+    }
+    Ok(())
+}
+
+/// Decompresses one entry into `out`, stopping as soon as the entry or the
+/// running archive total (`total`) passes the limits. Returns the entry size.
+pub fn copy_entry<W: Write>(
+    file: &mut ZipFile,
+    out: &mut W,
+    limits: &ZipLimits,
+    total: &mut u64,
+) -> io::Result<u64> {
+    let compressed = file.compressed_size().max(1);
+    let ratio_limit = ((compressed as f64 * limits.max_ratio) as u64).max(limits.ratio_min_size);
+    let entry_limit = limits.max_entry_size.min(ratio_limit);
+    let total_left = limits.max_total_size.saturating_sub(*total);
+
+    if file.size() > entry_limit {
+        return Err(limit_error(format!(
+            "declared size {} exceeds the entry limit of {}",
+            file.size(),
+            entry_limit
+        )));
+    }
+
+    // Read one byte past the tightest limit so an overrun is detected
+    // whatever the archive claims
+    let allowed = entry_limit.min(total_left);
+    let written = io::copy(&mut file.take(allowed + 1), out)?;
+    if written > entry_limit {
+        return Err(limit_error(format!(
+            "entry expands past {} bytes ({} compressed)",
+            entry_limit, compressed
+        )));
+    }
+    if written > total_left {
+        return Err(total_limit_error(format!(
+            "archive expands past the total limit of {}",
+            limits.max_total_size
+        )));
+    }
+    *total += written;
+    Ok(written)
+}
+
+fn file_hash(path: &Path) -> io::Result<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Extracts every archive below `options.data_dir` into `options.output_dir`.
+pub fn unzip(options: &ExtractOptions) -> io::Result<ExtractLog> {
+    let mut log = ExtractLog::default();
+    fs::create_dir_all(&options.output_dir)?;
+
+    let archives = files_under(&options.data_dir)?.into_iter().filter(|path| {
+        path.extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+    });
+    for archive_path in archives {
+        if let Err(e) = extract_archive(&archive_path, options, &mut log) {
+            log.push(
+                &archive_path,
+                None,
+                None,
+                Action::Rejected,
+                Some(e.to_string()),
+            );
+        }
+    }
+    Ok(log)
+}
+
+fn extract_archive(
+    archive_path: &Path,
+    options: &ExtractOptions,
+    log: &mut ExtractLog,
+) -> io::Result<()> {
+    let file = fs::File::open(archive_path)?;
+    let mut archive = zip::ZipArchive::new(file)?;
+    check_entry_count(&archive, &options.limits)?;
+
+    let mut total = 0;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = file.name().to_string();
+        let relative = match file.enclosed_name() {
+            Some(path) => path.to_owned(),
+            None => {
+                log.push(
+                    archive_path,
+                    Some(&name),
+                    None,
+                    Action::Rejected,
+                    Some("entry path escapes the output directory".to_string()),
+                );
+                continue;
+            }
+        };
+        let outpath = options.output_dir.join(relative);
+
+        let comment = file.comment();
+        if !comment.is_empty() {
+            debug!("File {i} comment: {comment}");
+        }
+
+        if file.is_dir() {
+            fs::create_dir_all(&outpath)?;
+            continue;
+        }
+        if let Some(p) = outpath.parent() {
+            fs::create_dir_all(p)?;
+        }
+
+        // Decompress beside the target so a rejected entry never replaces
+        // or truncates an existing file
+        let mut partial = outpath.clone().into_os_string();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        let copied = fs::File::create(&partial)
+            .and_then(|mut out| copy_entry(&mut file, &mut out, &options.limits, &mut total));
+        if let Err(e) = copied {
+            // Creating the partial file may itself have failed
+            match fs::remove_file(&partial) {
+                Err(remove) if remove.kind() != io::ErrorKind::NotFound => return Err(remove),
+                _ => {}
+            }
+            if e.kind() == io::ErrorKind::FileTooLarge {
+                return Err(e);
+            }
+            log.push(
+                archive_path,
+                Some(&name),
+                None,
+                Action::Rejected,
+                Some(e.to_string()),
+            );
+            continue;
+        }
+
+        let (target, action) = match (outpath.exists(), options.collisions) {
+            (false, _) => (outpath.clone(), Action::Extracted),
+            (true, CollisionPolicy::Skip) => (outpath.clone(), Action::SkippedExisting),
+            (true, CollisionPolicy::Overwrite) => (outpath.clone(), Action::Overwritten),
+            (true, CollisionPolicy::KeepBoth) => (unused_path(&outpath), Action::KeptBoth),
+            (true, CollisionPolicy::CompareHash) => {
+                if file_hash(&partial)? == file_hash(&outpath)? {
+                    (outpath.clone(), Action::SkippedIdentical)
+                } else {
+                    (unused_path(&outpath), Action::KeptBoth)
+                }
+            }
+        };
+
+        match action {
+            Action::SkippedExisting | Action::SkippedIdentical => fs::remove_file(&partial)?,
+            _ => {
+                fs::rename(&partial, &target)?;
+                if options.apply_permissions {
+                    apply_permissions(&file, &target)?;
+                }
+            }
+        }
+        log.push(archive_path, Some(&name), Some(&target), action, None);
+    }
+    Ok(())
+}
+
+// Permissions recorded in the archive, only applied when asked for
+fn apply_permissions(file: &ZipFile, path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Some(mode) = file.unix_mode() {
+            // Never let an archive make files setuid, setgid or world writable
+            let mode = mode & 0o775;
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (file, path);
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use log::{info, warn};
+use percent_encoding::percent_decode_str;
+use reqwest::Url;
+use scraper::{Html, Selector};
+use std::error::Error;
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+
+// Listing page for the Racing Queensland thoroughbred sectionals
+pub const SECTIONALS_URL: &str =
+    "https://www.racingqueensland.com.au/industry/thoroughbred/thoroughbred-sectionals";
+
+pub struct FetchOptions {
+    pub listing_url: String,
+    pub data_dir: PathBuf,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            listing_url: SECTIONALS_URL.to_string(),
+            data_dir: PathBuf::from("./data"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FetchSummary {
+    pub downloaded: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+    pub invalid: Vec<(Url, String)>,
+}
+
+/// Collects every link on the listing page that points at a zip archive,
+/// resolved against `base` and in page order without duplicates.
+pub fn find_zip_links(html: &str, base: &Url) -> Vec<Url> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("a[href]").unwrap();
+
+    let mut links: Vec<Url> = Vec::new();
+    for element in document.select(&selector) {
+        let href = match element.value().attr("href") {
+            Some(href) => href.trim(),
+            None => continue,
+        };
+        let url = match base.join(href) {
+            Ok(url) => url,
+            Err(_) => continue,
+        };
+        if url.path().to_lowercase().ends_with(".zip") && !links.contains(&url) {
+            links.push(url);
+        }
+    }
+    links
+}
+
+/// File name of the archive behind `url`, percent-decoded.
+pub fn archive_name(url: &Url) -> Option<String> {
+    let segment = url.path_segments()?.next_back()?;
+    let name = percent_decode_str(segment).decode_utf8().ok()?.into_owned();
+    if name.is_empty() || name.contains(['/', '\\']) || name == ".." {
+        return None;
+    }
+    Some(name)
+}
+
+/// Where an archive is stored: `<data_dir>/<yyyy>/<mm>/<name>` when the name
+/// starts with a `yyyymmdd` date, otherwise `<data_dir>/undated/<name>`.
+pub fn archive_path(data_dir: &Path, name: &str) -> PathBuf {
+    let mut path = data_dir.to_path_buf();
+    let date = name
+        .get(..8)
+        .and_then(|prefix| chrono::NaiveDate::parse_from_str(prefix, "%Y%m%d").ok());
+    match date {
+        Some(date) => {
+            path.push(date.format("%Y").to_string());
+            path.push(date.format("%m").to_string());
+        }
+        None => path.push("undated"),
+    }
+    path.push(name);
+    path
+}
+
+/// Checks that `bytes` is a readable zip archive whose entries all pass
+/// their CRC check.
+pub fn verify_zip(bytes: &[u8]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+    if archive.is_empty() {
+        return Err("archive has no entries".into());
+    }
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        io::copy(&mut file, &mut io::sink())?;
+    }
+    Ok(())
+}
+
+async fn download(client: &reqwest::Client, url: &Url) -> reqwest::Result<Vec<u8>> {
+    let response = client.get(url.clone()).send().await?.error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Downloads every archive linked from the listing page that is not already
+/// stored under `data_dir`. Archives that fail to download or verify are
+/// listed in `invalid` without stopping the others.
+pub async fn fetch_sectionals(
+    options: &FetchOptions,
+) -> Result<FetchSummary, Box<dyn Error + Send + Sync>> {
+    let client = reqwest::Client::new();
+    let base = Url::parse(&options.listing_url)?;
+    let html = client
+        .get(base.clone())
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    let mut summary = FetchSummary::default();
+    for url in find_zip_links(&html, &base) {
+        let name = match archive_name(&url) {
+            Some(name) => name,
+            None => {
+                summary
+                    .invalid
+                    .push((url, "link has no usable file name".to_string()));
+                continue;
+            }
+        };
+        let path = archive_path(&options.data_dir, &name);
+        if path.exists() {
+            summary.skipped.push(path);
+            continue;
+        }
+
+        info!("Downloading: {}", url);
+        let bytes = match download(&client, &url).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!("Failed {}: {}", url, e);
+                summary.invalid.push((url, e.to_string()));
+                continue;
+            }
+        };
+
+        if let Err(e) = verify_zip(&bytes) {
+            warn!("Rejected {}: {}", url, e);
+            summary.invalid.push((url, e.to_string()));
+            continue;
+        }
+
+        // Write beside the target first so an interrupted run never leaves a
+        // truncated archive that would be skipped next time
+        if let Some(p) = path.parent() {
+            fs::create_dir_all(p)?;
+        }
+        let partial = path.with_extension("zip.part");
+        fs::write(&partial, &bytes)?;
+        fs::rename(&partial, &path)?;
+        summary.downloaded.push(path);
+    }
+
+    Ok(summary)
+}
@@ -0,0 +1,187 @@
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Type of a named field, used to type output columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    Bool,
+    Int,
+    Float,
+    Text,
+    Date,
+    Time,
+}
+
+/// A single typed value read from one of the summary structs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Date(NaiveDate),
+    Time(NaiveTime),
+}
+
+impl FieldValue {
+    pub fn is_null(&self) -> bool {
+        matches!(self, FieldValue::Null)
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            FieldValue::Int(value) => Some(*value as f64),
+            FieldValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// An integer converted to `T`, or `None` when it does not fit.
+    pub fn as_int<T: TryFrom<i64>>(&self) -> Option<T> {
+        match self {
+            FieldValue::Int(value) => T::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            FieldValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FieldValue::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_date(&self) -> Option<NaiveDate> {
+        match self {
+            FieldValue::Date(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_time(&self) -> Option<NaiveTime> {
+        match self {
+            FieldValue::Time(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+// Matches the `to_string()` of the underlying field, nulls are empty
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Null => Ok(()),
+            FieldValue::Bool(value) => value.fmt(f),
+            FieldValue::Int(value) => value.fmt(f),
+            FieldValue::Float(value) => value.fmt(f),
+            FieldValue::Text(value) => value.fmt(f),
+            FieldValue::Date(value) => value.fmt(f),
+            FieldValue::Time(value) => value.fmt(f),
+        }
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(value: bool) -> Self {
+        FieldValue::Bool(value)
+    }
+}
+
+impl From<i32> for FieldValue {
+    fn from(value: i32) -> Self {
+        FieldValue::Int(value as i64)
+    }
+}
+
+impl From<u8> for FieldValue {
+    fn from(value: u8) -> Self {
+        FieldValue::Int(value as i64)
+    }
+}
+
+impl From<usize> for FieldValue {
+    fn from(value: usize) -> Self {
+        FieldValue::Int(value as i64)
+    }
+}
+
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        FieldValue::Float(value)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        FieldValue::Text(value.to_string())
+    }
+}
+
+impl From<NaiveDate> for FieldValue {
+    fn from(value: NaiveDate) -> Self {
+        FieldValue::Date(value)
+    }
+}
+
+impl From<NaiveTime> for FieldValue {
+    fn from(value: NaiveTime) -> Self {
+        FieldValue::Time(value)
+    }
+}
+
+impl<T: Into<FieldValue>> From<Option<T>> for FieldValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(FieldValue::Null, Into::into)
+    }
+}
+
+/// Error for a value that does not fit the field it is set on.
+pub fn invalid_value(name: &str, value: &FieldValue) -> String {
+    format!("invalid value {:?} for {}", value, name)
+}
+
+/// Seconds since midnight of a time, which for race times is the elapsed
+/// time in seconds.
+pub fn seconds(time: &NaiveTime) -> f64 {
+    (*time - NaiveTime::MIN).num_milliseconds() as f64 / 1000.0
+}
+
+/// The race time of `seconds` elapsed, to the millisecond, or `None` for a
+/// time outside a day.
+pub fn from_seconds(seconds: f64) -> Option<NaiveTime> {
+    if !(0.0..86400.0).contains(&seconds) {
+        return None;
+    }
+    let millis = (seconds * 1000.0).round() as u32;
+    NaiveTime::from_num_seconds_from_midnight_opt(millis / 1000, millis % 1000 * 1_000_000)
+}
+
+/// Read access to the fields of a summary struct by their snake_case name,
+/// as used for column headers and schema files.
+pub trait Fields {
+    /// Every field name with its type, in output order.
+    const FIELDS: &'static [(&'static str, FieldKind)];
+
+    /// The value of `name`, or `None` when there is no such field.
+    fn field(&self, name: &str) -> Option<FieldValue>;
+
+    /// Sets `name` from a value of its kind, as read back from an export.
+    fn set_field(&mut self, name: &str, value: &FieldValue) -> Result<(), String>;
+
+    fn field_kind(name: &str) -> Option<FieldKind> {
+        Self::FIELDS
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, kind)| *kind)
+    }
+}
@@ -0,0 +1,572 @@
+use crate::convert::{compare_horses, compare_races};
+use crate::error::{ErrorKind, ParseError};
+use crate::export::long::{HORSE_KEYS, RACE_KEYS};
+use crate::export::schema::{Schema, SectionRef, Source, SPLIT_FIELDS};
+use crate::export::text::DEFAULT_NULL;
+use crate::fields::{from_seconds, FieldKind, FieldValue, Fields};
+use crate::report::RunReport;
+use crate::structs::fssummary::FastestSectionSummary;
+use crate::structs::horsesummary::HorseSummary;
+use crate::structs::racesummary::RaceSummary;
+use crate::structs::sectionsummary::SectionSummary;
+use chrono::{NaiveDate, NaiveTime};
+use csv::{ReaderBuilder, StringRecord};
+use log::{error, info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Which export a CSV file was written by, told apart by its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvLayout {
+    /// One row per runner with the columns of a schema
+    Runners,
+    /// `--table sections`, one row per runner per section
+    Sections,
+}
+
+/// What `CsvImport::read` made of one file.
+#[derive(Debug, Clone)]
+pub struct CsvRead {
+    pub layout: CsvLayout,
+    /// Rows read into races
+    pub rows: usize,
+    /// Rows left out, by line number with the reason
+    pub skipped: Vec<(u64, String)>,
+}
+
+// Where the value of a column goes
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    Race(String),
+    Horse(String),
+    // A field of the section starting this many metres from the finish
+    Split(i32, String),
+    // The race's fastest time for that split
+    SplitFastest(i32),
+    // Race distance, from section[-1].cumulated_distance
+    Distance,
+    SectionNumber,
+    Section(String),
+    SectionFastest,
+}
+
+struct ColumnTarget {
+    target: Target,
+    kind: FieldKind,
+    // chrono format of the schema column, if any
+    format: Option<String>,
+    null: Option<String>,
+}
+
+impl ColumnTarget {
+    fn new(target: Target, kind: FieldKind) -> Self {
+        Self {
+            target,
+            kind,
+            format: None,
+            null: None,
+        }
+    }
+}
+
+// Headers compared without case or separators, so that headers written
+// with any --header-case still match
+fn normalise(header: &str) -> String {
+    header
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+// Columns computed from others on export, skipped without a warning
+fn is_derived(header: &str) -> bool {
+    let key = normalise(header);
+    key == "distancetofinish"
+        || key == "runstyle"
+        || key == "wide"
+        || key.starts_with("groundloss")
+        || key == "groundadjustedfinishtime"
+        || key.ends_with("deficit")
+        || key.starts_with("finishingspeed")
+}
+
+// The delimiter that splits the header line into the most columns
+fn sniff_delimiter(text: &str) -> u8 {
+    let header = text.lines().next().unwrap_or_default();
+    [b',', b';', b'\t', b'|']
+        .into_iter()
+        .max_by_key(|delimiter| header.bytes().filter(|b| b == delimiter).count())
+        .unwrap_or(b',')
+}
+
+/// Elapsed time written as `hh:mm:ss.fff`, `mm:ss.fff` or seconds.
+fn parse_time(text: &str) -> Option<NaiveTime> {
+    let parts: Vec<&str> = text.split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    let mut seconds = 0.0;
+    for part in parts {
+        seconds = seconds * 60.0 + part.trim().parse::<f64>().ok()?;
+    }
+    from_seconds(seconds)
+}
+
+fn parse_value(text: &str, kind: FieldKind, format: Option<&str>) -> Option<FieldValue> {
+    let value = match kind {
+        FieldKind::Bool => FieldValue::Bool(text.parse().ok()?),
+        FieldKind::Int => FieldValue::Int(text.parse().ok()?),
+        FieldKind::Float => FieldValue::Float(text.parse().ok()?),
+        FieldKind::Text => FieldValue::Text(text.to_string()),
+        FieldKind::Date => {
+            FieldValue::Date(NaiveDate::parse_from_str(text, format.unwrap_or("%Y-%m-%d")).ok()?)
+        }
+        FieldKind::Time => FieldValue::Time(match format {
+            Some(format) => NaiveTime::parse_from_str(text, format).ok()?,
+            None => parse_time(text)?,
+        }),
+    };
+    Some(value)
+}
+
+fn elapsed_between(later: NaiveTime, earlier: NaiveTime) -> NaiveTime {
+    if later > earlier {
+        NaiveTime::MIN + (later - earlier)
+    } else {
+        NaiveTime::MIN
+    }
+}
+
+/// Where each split starting `metres` before the finish ends, for splits of
+/// the length of the one nearest the finish.
+fn split_ends(distance: i32, metres: &[i32]) -> Vec<(i32, i32)> {
+    let mut metres = metres.to_vec();
+    metres.sort_unstable_by(|a, b| b.cmp(a));
+    let length = metres.last().copied().unwrap_or(0);
+    metres
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let next_start = metres.get(i + 1).map_or(distance, |next| distance - next);
+            (*m, (distance - m + length).min(next_start))
+        })
+        .collect()
+}
+
+/// Runner sections from the split columns of a runner CSV. Running not
+/// covered by a split, such as everything before the first one, becomes a
+/// single section so that distances from the finish still line up.
+fn split_sections(distance: i32, mut splits: BTreeMap<i32, SectionSummary>) -> Vec<SectionSummary> {
+    let metres: Vec<i32> = splits.keys().copied().collect();
+    let mut sections: Vec<SectionSummary> = Vec::new();
+    for (m, end) in split_ends(distance, &metres) {
+        let Some(mut split) = splits.remove(&m) else {
+            continue;
+        };
+        let start = distance - m;
+        let covered = sections.last().map_or(0, |s| s.cumulated_distance);
+        if start > covered && split.intermediate_time > NaiveTime::MIN {
+            let mut gap = SectionSummary::new();
+            gap.cumulated_distance = start;
+            gap.intermediate_time = elapsed_between(split.intermediate_time, split.section_time);
+            let before = sections
+                .last()
+                .map_or(NaiveTime::MIN, |s| s.intermediate_time);
+            gap.section_time = elapsed_between(gap.intermediate_time, before);
+            sections.push(gap);
+        }
+        split.cumulated_distance = end;
+        sections.push(split);
+    }
+    sections
+}
+
+/// Rebuilds races from the CSV files this tool exports, as far as their
+/// columns allow. Runner CSVs give race and runner fields and the split
+/// sections; sections CSVs give every section of every runner, replacing the
+/// splits of runners found in both.
+pub struct CsvImport {
+    schema: Schema,
+    races: Vec<(PathBuf, RaceSummary)>,
+    index: HashMap<i32, usize>,
+    distances: HashMap<i32, i32>,
+    // By race and horse code, then by metres from the finish
+    splits: HashMap<(i32, i32), BTreeMap<i32, SectionSummary>>,
+    // By race code and metres from the finish
+    split_fastest: HashMap<(i32, i32), NaiveTime>,
+    // By race and horse code, then by section number
+    sections: HashMap<(i32, i32), BTreeMap<i64, SectionSummary>>,
+    // By race code and cumulated distance
+    section_fastest: HashMap<(i32, i32), NaiveTime>,
+}
+
+impl CsvImport {
+    /// `schema` is the column spec runner CSVs were written with; columns
+    /// it does not know are matched as `last_<metres>_<field>` splits or
+    /// skipped.
+    pub fn new(schema: Schema) -> Self {
+        Self {
+            schema,
+            races: Vec::new(),
+            index: HashMap::new(),
+            distances: HashMap::new(),
+            splits: HashMap::new(),
+            split_fastest: HashMap::new(),
+            sections: HashMap::new(),
+            section_fastest: HashMap::new(),
+        }
+    }
+
+    fn runner_target(&self, header: &str) -> Option<ColumnTarget> {
+        let key = normalise(header);
+        let found = self
+            .schema
+            .spec
+            .columns
+            .iter()
+            .zip(self.schema.sources())
+            .find(|(column, _)| normalise(&column.header) == key);
+        let Some((column, source)) = found else {
+            return split_target(&key);
+        };
+
+        let target = match source {
+            Source::Race(field) => Target::Race(field.clone()),
+            Source::Horse(field) if HorseSummary::field_kind(field).is_some() => {
+                Target::Horse(field.clone())
+            }
+            Source::Horse(_) => return None,
+            Source::Section(SectionRef::FromFinish(m), field) => match field.as_str() {
+                "race_fastest_time" => Target::SplitFastest(*m),
+                "deficit" => return None,
+                _ => Target::Split(*m, field.clone()),
+            },
+            Source::Section(SectionRef::Index(-1), field) if field == "cumulated_distance" => {
+                Target::Distance
+            }
+            Source::Section(SectionRef::Index(_), _) => return None,
+        };
+        Some(ColumnTarget {
+            target,
+            kind: source.kind()?,
+            format: column.format.clone(),
+            null: column.null.clone(),
+        })
+    }
+
+    fn section_target(header: &str) -> Option<ColumnTarget> {
+        let key = normalise(header);
+        if let Some(field) = RACE_KEYS.iter().find(|field| normalise(field) == key) {
+            let kind = RaceSummary::field_kind(field)?;
+            return Some(ColumnTarget::new(Target::Race(field.to_string()), kind));
+        }
+        if let Some((_, field)) = HORSE_KEYS
+            .iter()
+            .find(|(header, _)| normalise(header) == key)
+        {
+            let kind = HorseSummary::field_kind(field)?;
+            return Some(ColumnTarget::new(Target::Horse(field.to_string()), kind));
+        }
+        if let Some((field, kind)) = SectionSummary::FIELDS
+            .iter()
+            .find(|(field, _)| normalise(field) == key)
+        {
+            return Some(ColumnTarget::new(Target::Section(field.to_string()), *kind));
+        }
+        match key.as_str() {
+            "sectionnumber" => Some(ColumnTarget::new(Target::SectionNumber, FieldKind::Int)),
+            "racefastesttime" => Some(ColumnTarget::new(Target::SectionFastest, FieldKind::Time)),
+            _ => None,
+        }
+    }
+
+    /// Reads one exported CSV. A row with a value that does not parse, or
+    /// that cannot be stored, is left out and listed in the result; a file
+    /// without race and horse code columns is an error. `path` is only used
+    /// to report where races came from.
+    pub fn read(
+        &mut self,
+        path: &Path,
+        text: &str,
+    ) -> Result<CsvRead, Box<dyn Error + Send + Sync>> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(sniff_delimiter(text))
+            .from_reader(text.as_bytes());
+        let headers = reader.headers()?.clone();
+        let layout = if headers.iter().any(|h| normalise(h) == "sectionnumber") {
+            CsvLayout::Sections
+        } else {
+            CsvLayout::Runners
+        };
+
+        let mut columns = Vec::new();
+        for header in &headers {
+            let target = match layout {
+                CsvLayout::Runners => self.runner_target(header),
+                CsvLayout::Sections => Self::section_target(header),
+            };
+            if target.is_none() && !is_derived(header) {
+                warn!("{:?}: skipping column {:?}", path, header);
+            }
+            columns.push(target);
+        }
+        let has = |target: &Target| columns.iter().flatten().any(|c| c.target == *target);
+        for (target, name) in [
+            (Target::Race("race_code".to_string()), "race code"),
+            (Target::Horse("code".to_string()), "horse code"),
+        ] {
+            if !has(&target) {
+                return Err(format!("{:?}: no {} column", path, name).into());
+            }
+        }
+
+        let mut read = CsvRead {
+            layout,
+            rows: 0,
+            skipped: Vec::new(),
+        };
+        for record in reader.records() {
+            let (line, row) = match record {
+                Ok(record) => (
+                    record.position().map_or(0, |p| p.line()),
+                    self.row_values(&columns, &headers, &record)
+                        .and_then(|values| self.add_row(path, &values)),
+                ),
+                Err(e) => (e.position().map_or(0, |p| p.line()), Err(e.to_string())),
+            };
+            match row {
+                Ok(()) => read.rows += 1,
+                Err(e) => {
+                    warn!("{:?} line {}: skipping row, {}", path, line, e);
+                    read.skipped.push((line, e));
+                }
+            }
+        }
+        Ok(read)
+    }
+
+    // The parsed values of a row's known columns, leaving out missing ones
+    fn row_values<'c>(
+        &self,
+        columns: &'c [Option<ColumnTarget>],
+        headers: &StringRecord,
+        record: &StringRecord,
+    ) -> Result<Vec<(&'c Target, FieldValue)>, String> {
+        let mut values = Vec::new();
+        for ((column, cell), header) in columns.iter().zip(record).zip(headers) {
+            let Some(column) = column else {
+                continue;
+            };
+            let is_null = cell.is_empty()
+                || cell == DEFAULT_NULL
+                || cell == self.schema.spec.null
+                || column.null.as_deref() == Some(cell);
+            if is_null {
+                continue;
+            }
+            let value = parse_value(cell, column.kind, column.format.as_deref())
+                .ok_or_else(|| format!("bad {:?} value {:?}", header, cell))?;
+            values.push((&column.target, value));
+        }
+        Ok(values)
+    }
+
+    fn add_row(&mut self, path: &Path, values: &[(&Target, FieldValue)]) -> Result<(), String> {
+        let find = |target: Target| {
+            values
+                .iter()
+                .find(|(t, _)| **t == target)
+                .and_then(|(_, value)| value.as_int::<i32>())
+        };
+        let race_code = find(Target::Race("race_code".to_string())).ok_or("no race code")?;
+        let horse_code = find(Target::Horse("code".to_string())).ok_or("no horse code")?;
+        let section_number = values.iter().find_map(|(target, value)| match target {
+            Target::SectionNumber => value.as_int::<i64>(),
+            _ => None,
+        });
+        let cumulated_distance = find(Target::Section("cumulated_distance".to_string()));
+
+        let position = *self.index.entry(race_code).or_insert_with(|| {
+            let mut race = RaceSummary::new();
+            race.race_code = race_code;
+            self.races.push((path.to_path_buf(), race));
+            self.races.len() - 1
+        });
+        let race = &mut self.races[position].1;
+        for (target, value) in values {
+            if let Target::Race(field) = target {
+                race.set_field(field, value)?;
+            }
+        }
+        let horse = match race.horses.iter().position(|h| h.code == horse_code) {
+            Some(i) => &mut race.horses[i],
+            None => {
+                let mut horse = HorseSummary::new();
+                horse.code = horse_code;
+                race.horses.push(horse);
+                race.horses.last_mut().unwrap()
+            }
+        };
+
+        let key = (race_code, horse_code);
+        for (target, value) in values {
+            match target {
+                Target::Horse(field) => horse.set_field(field, value)?,
+                Target::Split(metres, field) => self
+                    .splits
+                    .entry(key)
+                    .or_default()
+                    .entry(*metres)
+                    .or_insert_with(SectionSummary::new)
+                    .set_field(field, value)?,
+                Target::SplitFastest(metres) => {
+                    if let Some(time) = value.as_time() {
+                        self.split_fastest.insert((race_code, *metres), time);
+                    }
+                }
+                Target::Distance => {
+                    if let Some(distance) = value.as_int() {
+                        self.distances.insert(race_code, distance);
+                    }
+                }
+                Target::Section(field) => {
+                    let number = section_number.ok_or("no section number")?;
+                    self.sections
+                        .entry(key)
+                        .or_default()
+                        .entry(number)
+                        .or_insert_with(SectionSummary::new)
+                        .set_field(field, value)?
+                }
+                Target::SectionFastest => {
+                    if let (Some(time), Some(distance)) = (value.as_time(), cumulated_distance) {
+                        self.section_fastest.insert((race_code, distance), time);
+                    }
+                }
+                Target::Race(_) | Target::SectionNumber => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The races read so far, in output order, each with the file it first
+    /// appeared in.
+    pub fn finish(mut self) -> Vec<(PathBuf, RaceSummary)> {
+        for (_, race) in &mut self.races {
+            let code = race.race_code;
+            let distance = self.distances.get(&code).copied().or_else(|| {
+                race.horses
+                    .iter()
+                    .find(|h| h.distance_travelled > 0)
+                    .map(|h| h.distance_travelled - h.distance_difference)
+            });
+
+            for horse in &mut race.horses {
+                let key = (code, horse.code);
+                if let Some(sections) = self.sections.remove(&key) {
+                    horse.sections = sections.into_values().collect();
+                } else if let (Some(splits), Some(distance)) = (self.splits.remove(&key), distance)
+                {
+                    horse.sections = split_sections(distance, splits);
+                }
+            }
+
+            let mut fastest: Vec<(i32, NaiveTime)> = self
+                .section_fastest
+                .iter()
+                .filter(|((race_code, _), _)| *race_code == code)
+                .map(|((_, distance), time)| (*distance, *time))
+                .collect();
+            if let Some(distance) = distance.filter(|_| fastest.is_empty()) {
+                let splits: Vec<(i32, NaiveTime)> = self
+                    .split_fastest
+                    .iter()
+                    .filter(|((race_code, _), _)| *race_code == code)
+                    .map(|((_, metres), time)| (*metres, *time))
+                    .collect();
+                let metres: Vec<i32> = splits.iter().map(|(m, _)| *m).collect();
+                for (m, end) in split_ends(distance, &metres) {
+                    if let Some((_, time)) = splits.iter().find(|(split, _)| *split == m) {
+                        fastest.push((end, *time));
+                    }
+                }
+            }
+            fastest.sort_by_key(|(distance, _)| *distance);
+            race.fastest_sections = fastest
+                .into_iter()
+                .map(|(distance, time)| {
+                    let mut section = FastestSectionSummary::new();
+                    section.cumulated_distance = distance;
+                    section.section_time = time;
+                    section
+                })
+                .collect();
+
+            race.horses.sort_by(compare_horses);
+        }
+        self.races.sort_by(|a, b| compare_races(&a.1, &b.1));
+        self.races
+    }
+}
+
+// `last_<metres>_<suffix>` for the split columns written by --splits and
+// --fastest
+fn split_target(key: &str) -> Option<ColumnTarget> {
+    let rest = key.strip_prefix("last")?;
+    let digits = rest.chars().take_while(char::is_ascii_digit).count();
+    let metres: i32 = rest[..digits].parse().ok()?;
+    let suffix = &rest[digits..];
+    if suffix == "fastesttime" {
+        return Some(ColumnTarget::new(
+            Target::SplitFastest(metres),
+            FieldKind::Time,
+        ));
+    }
+    let (_, field) = SPLIT_FIELDS
+        .iter()
+        .find(|(name, _)| normalise(name) == suffix)?;
+    let kind = SectionSummary::field_kind(field)?;
+    Some(ColumnTarget::new(
+        Target::Split(metres, field.to_string()),
+        kind,
+    ))
+}
+
+/// Reads every file into one set of races, see `CsvImport`. Rows that are
+/// left out are recorded in `report`, as are files that cannot be read at
+/// all, which are copied into `quarantine_dir` when one is given; the other
+/// files are still read.
+pub fn import_csv_files(
+    paths: &[PathBuf],
+    schema: Schema,
+    report: &mut RunReport,
+    quarantine_dir: Option<&Path>,
+) -> io::Result<Vec<(PathBuf, RaceSummary)>> {
+    let mut import = CsvImport::new(schema);
+    for path in paths {
+        let read = fs::read_to_string(path)
+            .map_err(ParseError::from)
+            .and_then(|text| {
+                import
+                    .read(path, &text)
+                    .map_err(|e| ParseError::new(ErrorKind::InvalidValue, e.to_string()))
+            });
+        match read {
+            Ok(read) => {
+                info!("Read {} {:?} rows from {:?}", read.rows, read.layout, path);
+                for (line, message) in &read.skipped {
+                    report.add_skipped_row(path, *line, message);
+                }
+            }
+            Err(e) => {
+                error!("Failed: {:?} {}", path, e);
+                report.add_failed(path, &e, None, quarantine_dir)?;
+            }
+        }
+    }
+    Ok(import.finish())
+}
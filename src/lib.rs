@@ -1,4 +1,4 @@
-use csv::Writer;
+use error::{ErrorKind, ParseError};
 use quick_xml::events::{BytesStart, BytesText, Event};
 use quick_xml::reader::Reader;
 use serde_json::{self, Value};
@@ -6,8 +6,11 @@ use sqlx::PgPool;
 use std::error::Error;
 use std::fs;
 use std::io::Write;
-use std::{borrow::Cow, fs::File, io::BufReader};
-use std::{io, path::PathBuf};
+use std::io::{BufRead, BufReader};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
 use structs::fssummary::FastestSectionSummary;
 use structs::horse::Horse;
 use structs::horsesummary::HorseSummary;
@@ -15,6 +18,16 @@ use structs::race::Race;
 use structs::racesummary::RaceSummary;
 use structs::sectionsummary::SectionSummary;
 
+pub mod analysis;
+pub mod convert;
+pub mod error;
+pub mod export;
+pub mod extract;
+pub mod fetch;
+pub mod fields;
+pub mod import;
+pub mod load;
+pub mod report;
 pub mod structs;
 
 // Race Summary Tags
@@ -83,27 +96,23 @@ pub fn parse_start<T: Clone + ValueProcessor>(
     e: BytesStart,
     tag: &mut String,
     values: &mut T, // Where the Values are being stored
-) {
+) -> Result<(), ParseError> {
     // Get Any Attributes
-    e.attributes()
-        .with_checks(false)
-        .map(|x| x.unwrap().clone())
-        .map(|x| {
-            let key = String::from_utf8(x.key.0.to_owned()).unwrap();
-            let value =
-                String::from_utf8(<Cow<'_, [u8]> as Clone>::clone(&x.value).into_owned()).unwrap();
-            (key, value)
-        })
-        .for_each(|(mut key, value)| {
-            if key == TIME {
-                key = tag.clone();
-            }
-            let text = values.get_single_fields(key.as_str());
-            let _ = match text {
-                Some(_x) => values.set_single_fields(key.as_str(), value),
-                None => Ok(()),
-            };
-        });
+    for attribute in e.attributes().with_checks(false) {
+        let attribute = attribute.map_err(|e| ParseError::new(ErrorKind::Xml, e.to_string()))?;
+        let mut key = String::from_utf8(attribute.key.0.to_owned())
+            .map_err(|e| ParseError::new(ErrorKind::Encoding, e.to_string()))?;
+        let value = String::from_utf8(attribute.value.into_owned())
+            .map_err(|e| ParseError::new(ErrorKind::Encoding, e.to_string()))?;
+
+        if key == TIME {
+            key = tag.clone();
+        }
+        if values.get_single_fields(key.as_str()).is_some() {
+            store(values, &key, value)?;
+        }
+    }
+    Ok(())
 }
 
 pub fn parse_text<T: Clone + ValueProcessor>(
@@ -111,23 +120,51 @@ pub fn parse_text<T: Clone + ValueProcessor>(
     tag: &mut String,
     values: &mut T, // Where the Values are being stored
     txt: &mut String,
-) {
-    *txt = e.unescape().unwrap().into_owned();
+) -> Result<(), ParseError> {
+    *txt = unescape_text(&e)?;
     let tag_name_str = tag.as_str();
-    let text = values.get_single_fields(tag_name_str);
 
     // Store Text to Tag
-    let _ = match text {
-        Some(_x) => values.set_single_fields(tag_name_str, txt.to_string()),
-        None => Ok(()),
-    };
+    if values.get_single_fields(tag_name_str).is_some() {
+        store(values, tag_name_str, txt.to_string())?;
+    }
+    Ok(())
+}
+
+// Sets a field, reporting a value it rejects as `InvalidValue`
+fn store<T: ValueProcessor>(values: &mut T, field: &str, value: String) -> Result<(), ParseError> {
+    let message = format!("could not store {:?} in {}", value, field);
+    values
+        .set_single_fields(field, value)
+        .map_err(|e| ParseError::new(ErrorKind::InvalidValue, format!("{}: {}", message, e)))
 }
 
 pub fn parse_end(tag: &mut String) {
     *tag = String::new();
 }
 
-pub fn proccess_basic(reader: &mut Reader<BufReader<File>>) -> RaceSummary {
+fn unescape_text(e: &BytesText) -> Result<String, ParseError> {
+    e.unescape()
+        .map(|text| text.into_owned())
+        .map_err(|e| ParseError::new(ErrorKind::Xml, e.to_string()))
+}
+
+fn tag_name(name: &[u8]) -> Result<String, ParseError> {
+    String::from_utf8(name.to_vec())
+        .map_err(|e| ParseError::new(ErrorKind::Encoding, e.to_string()))
+}
+
+fn parse_number<N: std::str::FromStr>(e: &BytesText) -> Result<N, ParseError> {
+    let text = unescape_text(e)?;
+    text.trim().parse::<N>().map_err(|_| {
+        ParseError::new(
+            ErrorKind::InvalidValue,
+            format!("could not parse {:?} as a number", text),
+        )
+    })
+}
+
+pub fn proccess_basic<R: BufRead>(reader: &mut Reader<R>) -> Result<RaceSummary, ParseError> {
     let mut race: RaceSummary = RaceSummary::new();
     let mut fastests = FastestSectionSummary::new();
     let mut horses = HorseSummary::new();
@@ -143,10 +180,10 @@ pub fn proccess_basic(reader: &mut Reader<BufReader<File>>) -> RaceSummary {
     let mut structflag = RACE_SUMMARY;
 
     loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Decl(_)) => {}
+        let parsed = match reader.read_event_into(&mut buf) {
+            Ok(Event::Decl(_)) => Ok(()),
             Ok(Event::Start(e)) => {
-                tag = String::from_utf8((*e.name().0).to_vec()).unwrap();
+                tag = tag_name(e.name().0).map_err(|err| err.at(&tagflag))?;
                 tagflag.push(tag.to_string());
 
                 match tag.as_str() {
@@ -160,135 +197,141 @@ pub fn proccess_basic(reader: &mut Reader<BufReader<File>>) -> RaceSummary {
                     FASTEST_SECTIONS => parse_start(e, &mut tag, &mut fastests),
                     HORSES => parse_start(e, &mut tag, &mut horses),
                     SECTIONS => parse_start(e, &mut tag, &mut sectionsummary),
-                    _ => {
-                        parse_start(e, &mut tag, &mut race);
-                    }
+                    _ => parse_start(e, &mut tag, &mut race),
                 }
             }
             Ok(Event::Empty(e)) => {
-                tag = String::from_utf8((*e.name().0).to_vec()).unwrap();
+                tag = tag_name(e.name().0).map_err(|err| err.at(&tagflag))?;
                 match structflag {
                     FASTEST_SECTIONS => parse_start(e, &mut tag, &mut fastests),
                     HORSES => parse_start(e, &mut tag, &mut horses),
                     SECTIONS => parse_start(e, &mut tag, &mut sectionsummary),
-                    _ => {
-                        parse_start(e, &mut tag, &mut race);
-                    }
+                    _ => parse_start(e, &mut tag, &mut race),
                 }
             }
             Ok(Event::Text(e)) => match tagflag.last() {
                 Some(x) => match x.as_str() {
-                    ITEM1 => {
-                        item1 = e.unescape().unwrap().into_owned().parse().unwrap();
-                    }
-                    ITEM2 => {
-                        item2 = e.unescape().unwrap().into_owned().parse().unwrap();
-                    }
+                    ITEM1 => parse_number(&e).map(|value| item1 = value),
+                    ITEM2 => parse_number(&e).map(|value| item2 = value),
                     _ => match structflag {
                         FASTEST_SECTIONS => parse_text(e, &mut tag, &mut fastests, &mut txt),
                         HORSES => parse_text(e, &mut tag, &mut horses, &mut txt),
                         SECTIONS => parse_text(e, &mut tag, &mut sectionsummary, &mut txt),
-                        _ => {
-                            parse_text(e, &mut tag, &mut race, &mut txt);
-                        }
+                        _ => parse_text(e, &mut tag, &mut race, &mut txt),
                     },
                 },
-                None => {
-                    parse_text(e, &mut tag, &mut race, &mut txt);
-                }
+                None => parse_text(e, &mut tag, &mut race, &mut txt),
             },
-            Ok(Event::End(_e)) => match tagflag.pop() {
-                Some(x) => match x.as_str() {
-                    SECTION_SUMMARY => {
-                        if tagflag.last().unwrap() == FASTEST_SECTIONS {
+            Ok(Event::End(_e)) => {
+                let parent = tagflag.len().checked_sub(2).map(|i| tagflag[i].clone());
+                if let Some(x) = tagflag.pop() {
+                    match (x.as_str(), parent.as_deref()) {
+                        (SECTION_SUMMARY, Some(FASTEST_SECTIONS)) => {
                             race.add_fastest_section(fastests.clone());
                             fastests = FastestSectionSummary::new();
-                        } else {
-                            horses.add_section(sectionsummary.clone())
                         }
-                    }
-                    HORSE_SUMMARY => {
-                        race.add_horse(horses.clone());
-                        horses = HorseSummary::new();
-                    }
-                    SERIALIZABLE_TUPLE_OF_DOUBLE_DOUBLE | SERIALIZABLE_TUPLE_OF_DOUBLE_INT32 => {
-                        match tagflag.last().unwrap().as_str() {
-                            SPEEDS => {
-                                let _ =
-                                    horses.add_tuple_field(SPEEDS, (item1.clone(), item2.clone()));
-                            }
-                            RANKS => {
-                                let _ =
-                                    horses.add_tuple_field(RANKS, (item1.clone(), item2.clone()));
-                            }
-                            _ => {}
+                        (SECTION_SUMMARY, _) => horses.add_section(sectionsummary.clone()),
+                        (HORSE_SUMMARY, _) => {
+                            race.add_horse(horses.clone());
+                            horses = HorseSummary::new();
+                        }
+                        (
+                            SERIALIZABLE_TUPLE_OF_DOUBLE_DOUBLE
+                            | SERIALIZABLE_TUPLE_OF_DOUBLE_INT32,
+                            Some(series @ (SPEEDS | RANKS)),
+                        ) => {
+                            let _ = horses.add_tuple_field(series, (item1, item2));
                         }
+                        (SECTIONS, _) => structflag = HORSES,
+                        _ => {}
                     }
-                    SECTIONS => structflag = HORSES,
-                    _ => {}
-                },
-                None => {}
-            },
+                }
+                Ok(())
+            }
 
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Err(e) => Err(ParseError::new(
+                ErrorKind::Xml,
+                format!("error at position {}: {}", reader.buffer_position(), e),
+            )),
             Ok(Event::Eof) => break,
-            _ => (),
-        }
+            _ => Ok(()),
+        };
+        parsed.map_err(|err| err.at(&tagflag))?;
+        buf.clear();
     }
-    race
+    Ok(race)
 }
 
-pub fn unzip() {
-    for file in fs::read_dir("./data")
-        .unwrap()
-        .map(|res| res.map(|e| e.path()))
-    {
-        let file = fs::File::open(file.unwrap()).unwrap();
-        let mut archive = zip::ZipArchive::new(file).unwrap();
+/// Race number encoded in the file name, e.g. `20230818_Ipswich_T_R03.xml` -> 3.
+/// Numbered copies such as `20230818_Ipswich_T_R03.1.xml` keep their number.
+pub fn race_number_from_path(path: &Path) -> Result<i32, ParseError> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('.').next())
+        .and_then(|stem| stem.rsplit('_').next())
+        .and_then(|part| part.strip_prefix('R'))
+        .and_then(|number| number.parse().ok())
+        .ok_or_else(|| {
+            ParseError::new(
+                ErrorKind::FileName,
+                format!("no race number in file name {:?}", path),
+            )
+        })
+}
 
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i).unwrap();
-            let mut outpath = PathBuf::from("./unzipped_sectionals");
-            match file.enclosed_name() {
-                Some(path) => outpath.push(path.to_owned()),
-                None => continue,
-            };
+/// Parses one sectional XML document into a race, rejecting documents that do
+/// not carry a race code.
+pub fn parse_xml<R: BufRead>(input: R, race_number: i32) -> Result<RaceSummary, ParseError> {
+    let mut reader = Reader::from_reader(input);
+    reader.trim_text(true);
 
-            {
-                let comment = file.comment();
-                if !comment.is_empty() {
-                    println!("File {i} comment: {comment}");
-                }
-            }
+    let mut race = proccess_basic(&mut reader)?;
+    race.race_number = race_number;
 
-            if (*file.name()).ends_with('/') {
-                println!("File {} extracted to \"{}\"", i, outpath.display());
-                fs::create_dir_all(&outpath).unwrap();
-            } else {
-                println!(
-                    "File {} extracted to \"{}\" ({} bytes)",
-                    i,
-                    outpath.display(),
-                    file.size()
-                );
-                if let Some(p) = outpath.parent() {
-                    if !p.exists() {
-                        fs::create_dir_all(p).unwrap();
-                    }
-                }
-                let mut outfile = fs::File::create(&outpath).unwrap();
-                io::copy(&mut file, &mut outfile).unwrap();
+    if race.race_code == 0 {
+        return Err(ParseError::new(
+            ErrorKind::MissingRaceCode,
+            "document has no RaceCode",
+        ));
+    }
+    Ok(race)
+}
 
-                // Get and Set permissions
-                #[cfg(unix)]
-                {
-                    use std::os::unix::fs::PermissionsExt;
+/// Parses one sectional XML file, taking the race number from its name.
+pub fn parse_file(path: &Path) -> Result<RaceSummary, ParseError> {
+    let race_number = race_number_from_path(path)?;
+    let file = fs::File::open(path)?;
+    parse_xml(BufReader::new(file), race_number)
+}
 
-                    if let Some(mode) = file.unix_mode() {
-                        fs::set_permissions(&outpath, fs::Permissions::from_mode(mode)).unwrap();
-                    }
-                }
-            }
+/// Every file below `dir`, descending into sub-directories, in sorted order.
+pub fn files_under(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(files_under(&path)?);
+        } else if path.is_file() {
+            files.push(path);
         }
     }
+    files.sort();
+    Ok(files)
+}
+
+/// `path` itself when nothing exists there, otherwise the first free
+/// `<stem>.<n>.<ext>` beside it.
+pub fn unused_path(path: &Path) -> PathBuf {
+    let mut target = path.to_path_buf();
+    let mut copy = 1;
+    while target.exists() {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(ext) => format!("{}.{}.{}", stem, copy, ext.to_string_lossy()),
+            None => format!("{}.{}", stem, copy),
+        };
+        target = path.with_file_name(name);
+        copy += 1;
+    }
+    target
 }
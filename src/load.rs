@@ -0,0 +1,126 @@
+use crate::export::database::{replace_race_rows, Backend};
+use crate::report::RunReport;
+use crate::structs::racesummary::RaceSummary;
+use chrono::{Duration, NaiveTime};
+use log::{error, info};
+use sqlx::migrate::Migrator;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::{Postgres, Transaction};
+use std::error::Error;
+
+/// Schema migrations bundled into the binary from `migrations/postgres`.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Connects to `database_url` and applies any pending migrations.
+pub async fn connect(database_url: &str) -> Result<PgPool, Box<dyn Error + Send + Sync>> {
+    let pool = PgPoolOptions::new()
+        .max_connections(4)
+        .connect(database_url)
+        .await?;
+    MIGRATOR.run(&pool).await?;
+    Ok(pool)
+}
+
+// Race times are elapsed times, stored as intervals
+fn interval(time: NaiveTime) -> Duration {
+    time - NaiveTime::MIN
+}
+
+impl Backend for Postgres {
+    type Time = Duration;
+
+    fn time(time: &NaiveTime) -> Duration {
+        interval(*time)
+    }
+}
+
+/// Upserts every race, each in its own transaction. A race that is already
+/// loaded has its row updated and its runners, sections, series and fastest
+/// sections replaced, so loading the same input twice changes nothing. A race
+/// that fails is rolled back, logged and recorded in `report`, and the rest
+/// are still loaded. Returns the number of races loaded.
+pub async fn load_races(pool: &PgPool, races: &[RaceSummary], report: &mut RunReport) -> usize {
+    let mut loaded = 0;
+    for race in races {
+        match load_race(pool, race).await {
+            Ok(()) => {
+                loaded += 1;
+                info!(
+                    "Loaded: {}:{} {} ({})",
+                    race.event_date, race.course_name, race.race_number, race.race_code
+                );
+            }
+            Err(e) => {
+                error!("Failed to load race {}: {}", race.race_code, e);
+                report.add_failed_race(race, e.to_string());
+            }
+        }
+    }
+    loaded
+}
+
+async fn load_race(pool: &PgPool, race: &RaceSummary) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    insert_race(&mut tx, race).await?;
+    tx.commit().await
+}
+
+async fn insert_race(
+    tx: &mut Transaction<'_, Postgres>,
+    race: &RaceSummary,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO meetings (meeting_code, event_date, event_name, course_name, track_name)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (meeting_code) DO UPDATE SET
+             event_date = EXCLUDED.event_date,
+             event_name = EXCLUDED.event_name,
+             course_name = EXCLUDED.course_name,
+             track_name = EXCLUDED.track_name",
+    )
+    .bind(race.meeting_code)
+    .bind(race.event_date)
+    .bind(&race.event_name)
+    .bind(&race.course_name)
+    .bind(&race.track_name)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO races (race_code, meeting_code, race_number, race_name, distance,
+                            finish_time, track_condition, rail_position, early_split,
+                            late_split, pace, early_pace_index, late_pace_index)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+         ON CONFLICT (race_code) DO UPDATE SET
+             meeting_code = EXCLUDED.meeting_code,
+             race_number = EXCLUDED.race_number,
+             race_name = EXCLUDED.race_name,
+             distance = EXCLUDED.distance,
+             finish_time = EXCLUDED.finish_time,
+             track_condition = EXCLUDED.track_condition,
+             rail_position = EXCLUDED.rail_position,
+             early_split = EXCLUDED.early_split,
+             late_split = EXCLUDED.late_split,
+             pace = EXCLUDED.pace,
+             early_pace_index = EXCLUDED.early_pace_index,
+             late_pace_index = EXCLUDED.late_pace_index",
+    )
+    .bind(race.race_code)
+    .bind(race.meeting_code)
+    .bind(race.race_number)
+    .bind(&race.race_name)
+    .bind(race.distance())
+    .bind(interval(race.finish_time))
+    .bind(&race.track_condition)
+    .bind(&race.rail_position)
+    .bind(race.early_split.map(interval))
+    .bind(race.late_split.map(interval))
+    .bind(race.pace.map(|pace| pace.to_string()))
+    .bind(race.early_pace_index)
+    .bind(race.late_pace_index)
+    .execute(&mut **tx)
+    .await?;
+
+    replace_race_rows::<Postgres>(tx, race).await?;
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use crate::convert::STDIN;
+use crate::error::{ErrorKind, ParseError};
+use crate::structs::racesummary::RaceSummary;
+use crate::unused_path;
+use chrono::{Local, NaiveDateTime};
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Converted,
+    Failed,
+    /// A row of a CSV input that was left out, the rest of the file was read
+    SkippedRow,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub outcome: Outcome,
+    pub race_code: Option<i32>,
+    pub runners: Option<usize>,
+    pub error_kind: Option<ErrorKind>,
+    pub message: Option<String>,
+    pub element_path: Option<String>,
+    pub quarantined_to: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+    pub converted: usize,
+    pub failed: usize,
+    pub skipped_rows: usize,
+    pub files: Vec<FileReport>,
+}
+
+impl RunReport {
+    pub fn new() -> Self {
+        Self {
+            started_at: Local::now().naive_local(),
+            finished_at: None,
+            converted: 0,
+            failed: 0,
+            skipped_rows: 0,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn add_converted(&mut self, path: &Path, race: &RaceSummary) {
+        self.converted += 1;
+        self.files.push(FileReport {
+            path: path.to_path_buf(),
+            outcome: Outcome::Converted,
+            race_code: Some(race.race_code),
+            runners: Some(race.horses.len()),
+            error_kind: None,
+            message: None,
+            element_path: None,
+            quarantined_to: None,
+        });
+    }
+
+    /// Records a failed input, copying it into `quarantine_dir` when one is
+    /// given. `bytes` is the content of an input that is not a file of its
+    /// own, such as a zip entry or standard input, and is written instead.
+    pub fn add_failed(
+        &mut self,
+        path: &Path,
+        error: &ParseError,
+        bytes: Option<&[u8]>,
+        quarantine_dir: Option<&Path>,
+    ) -> io::Result<()> {
+        let quarantined_to = match (quarantine_dir, bytes) {
+            (Some(dir), Some(bytes)) => Some(quarantine_bytes(path, bytes, dir)?),
+            (Some(dir), None) if path.is_file() => Some(quarantine(path, dir)?),
+            _ => None,
+        };
+        self.failed += 1;
+        self.files.push(FileReport {
+            path: path.to_path_buf(),
+            outcome: Outcome::Failed,
+            race_code: None,
+            runners: None,
+            error_kind: Some(error.kind),
+            message: Some(error.message.clone()),
+            element_path: error.element_path.clone(),
+            quarantined_to,
+        });
+        Ok(())
+    }
+
+    /// Records a row of the CSV input `path` that was left out at `line`.
+    pub fn add_skipped_row(&mut self, path: &Path, line: u64, message: &str) {
+        self.skipped_rows += 1;
+        self.files.push(FileReport {
+            path: path.to_path_buf(),
+            outcome: Outcome::SkippedRow,
+            race_code: None,
+            runners: None,
+            error_kind: Some(ErrorKind::InvalidValue),
+            message: Some(format!("line {}: {}", line, message)),
+            element_path: None,
+            quarantined_to: None,
+        });
+    }
+
+    /// Marks the input `race` was parsed from as failed, for a race that
+    /// parsed but could not be written to a database.
+    pub fn add_failed_race(&mut self, race: &RaceSummary, message: String) {
+        let error = ParseError::new(ErrorKind::Database, message);
+        let file = self.files.iter_mut().find(|file| {
+            file.outcome == Outcome::Converted && file.race_code == Some(race.race_code)
+        });
+        match file {
+            Some(file) => {
+                self.converted -= 1;
+                file.outcome = Outcome::Failed;
+                file.error_kind = Some(error.kind);
+                file.message = Some(error.message);
+            }
+            None => self.files.push(FileReport {
+                path: PathBuf::new(),
+                outcome: Outcome::Failed,
+                race_code: Some(race.race_code),
+                runners: Some(race.horses.len()),
+                error_kind: Some(error.kind),
+                message: Some(error.message),
+                element_path: None,
+                quarantined_to: None,
+            }),
+        }
+        self.failed += 1;
+    }
+
+    pub fn write(&mut self, path: &Path) -> io::Result<()> {
+        self.finished_at = Some(Local::now().naive_local());
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+}
+
+impl Default for RunReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Free path in `dir` for the file name of `path`
+fn quarantine_path(path: &Path, dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let name = if path == Path::new(STDIN) {
+        OsStr::new("stdin")
+    } else {
+        path.file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?
+    };
+    Ok(unused_path(&dir.join(name)))
+}
+
+/// Copies `path` into `dir`, numbering the copy if a file of the same name
+/// was already quarantined.
+pub fn quarantine(path: &Path, dir: &Path) -> io::Result<PathBuf> {
+    let target = quarantine_path(path, dir)?;
+    fs::copy(path, &target)?;
+    Ok(target)
+}
+
+/// Writes the content of an input without a file of its own, such as the
+/// zip entry `path`, into `dir` under the entry's name.
+pub fn quarantine_bytes(path: &Path, bytes: &[u8], dir: &Path) -> io::Result<PathBuf> {
+    let target = quarantine_path(path, dir)?;
+    fs::write(&target, bytes)?;
+    Ok(target)
+}
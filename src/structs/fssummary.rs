@@ -1,3 +1,4 @@
+use crate::fields::{invalid_value, FieldKind, FieldValue, Fields};
 use crate::ValueProcessor;
 use crate::*;
 use chrono::NaiveTime;
@@ -60,3 +61,32 @@ impl ValueProcessor for FastestSectionSummary {
         }
     }
 }
+
+impl Fields for FastestSectionSummary {
+    const FIELDS: &'static [(&'static str, FieldKind)] = &[
+        ("cumulated_distance", FieldKind::Int),
+        ("intermediate_time", FieldKind::Time),
+        ("section_time", FieldKind::Time),
+    ];
+
+    fn field(&self, name: &str) -> Option<FieldValue> {
+        let value = match name {
+            "cumulated_distance" => self.cumulated_distance.into(),
+            "intermediate_time" => self.intermediate_time.into(),
+            "section_time" => self.section_time.into(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    fn set_field(&mut self, name: &str, value: &FieldValue) -> Result<(), String> {
+        let invalid = || invalid_value(name, value);
+        match name {
+            "cumulated_distance" => self.cumulated_distance = value.as_int().ok_or_else(invalid)?,
+            "intermediate_time" => self.intermediate_time = value.as_time().ok_or_else(invalid)?,
+            "section_time" => self.section_time = value.as_time().ok_or_else(invalid)?,
+            _ => return Err(format!("no field {}", name)),
+        }
+        Ok(())
+    }
+}
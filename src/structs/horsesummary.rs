@@ -1,4 +1,5 @@
 use super::sectionsummary::SectionSummary;
+use crate::fields::{invalid_value, FieldKind, FieldValue, Fields};
 use crate::ValueProcessor;
 use crate::*;
 use chrono::NaiveTime;
@@ -78,6 +79,26 @@ impl HorseSummary {
     pub fn add_section(&mut self, new: SectionSummary) {
         self.sections.push(new)
     }
+
+    /// Each section paired with the distance from the finish at which it
+    /// starts, for a race over `race_distance` metres.
+    pub fn sections_from_finish(
+        &self,
+        race_distance: i32,
+    ) -> impl Iterator<Item = (i32, &SectionSummary)> {
+        let starts = std::iter::once(0).chain(self.sections.iter().map(|s| s.cumulated_distance));
+        starts
+            .zip(&self.sections)
+            .map(move |(start, section)| (race_distance - start, section))
+    }
+
+    /// The section starting `metres` before the finish, e.g. 600 for the
+    /// 600m to 400m split when sections are 200m long.
+    pub fn section_from_finish(&self, race_distance: i32, metres: i32) -> Option<&SectionSummary> {
+        self.sections_from_finish(race_distance)
+            .find(|(from_finish, _)| *from_finish == metres)
+            .map(|(_, section)| section)
+    }
 }
 
 impl ValueProcessor for HorseSummary {
@@ -238,3 +259,79 @@ impl ValueProcessor for HorseSummary {
         }
     }
 }
+
+impl Fields for HorseSummary {
+    const FIELDS: &'static [(&'static str, FieldKind)] = &[
+        ("name", FieldKind::Text),
+        ("code", FieldKind::Int),
+        ("bib", FieldKind::Int),
+        ("draw_number", FieldKind::Int),
+        ("distance_travelled", FieldKind::Int),
+        ("distance_difference", FieldKind::Int),
+        ("final_rank", FieldKind::Int),
+        ("time_official", FieldKind::Bool),
+        ("official_margin", FieldKind::Float),
+        ("fastest_section_time", FieldKind::Time),
+        ("fastest_section_index", FieldKind::Int),
+        ("top_speed", FieldKind::Float),
+        ("top_speed_index", FieldKind::Int),
+        ("finish_time", FieldKind::Time),
+        ("result_state", FieldKind::Text),
+        ("result_substate", FieldKind::Text),
+    ];
+
+    fn field(&self, name: &str) -> Option<FieldValue> {
+        let value = match name {
+            "name" => self.name.as_str().into(),
+            "code" => self.code.into(),
+            "bib" => self.bib.into(),
+            "draw_number" => self.draw_number.into(),
+            "distance_travelled" => self.distance_travelled.into(),
+            "distance_difference" => self.distance_difference.into(),
+            "final_rank" => self.final_rank.into(),
+            "time_official" => self.time_official.into(),
+            "official_margin" => self.official_margin.into(),
+            "fastest_section_time" => self.fastest_section_time.into(),
+            "fastest_section_index" => self.fastest_section_index.into(),
+            "top_speed" => self.top_speed.into(),
+            "top_speed_index" => self.top_speed_index.into(),
+            "finish_time" => self.finish_time.into(),
+            "result_state" => self.result_state.as_str().into(),
+            "result_substate" => self.result_substate.as_str().into(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    fn set_field(&mut self, name: &str, value: &FieldValue) -> Result<(), String> {
+        let invalid = || invalid_value(name, value);
+        match name {
+            "name" => self.name = value.as_str().ok_or_else(invalid)?.to_string(),
+            "code" => self.code = value.as_int().ok_or_else(invalid)?,
+            "bib" => self.bib = value.as_int().ok_or_else(invalid)?,
+            "draw_number" => self.draw_number = value.as_int().ok_or_else(invalid)?,
+            "distance_travelled" => self.distance_travelled = value.as_int().ok_or_else(invalid)?,
+            "distance_difference" => {
+                self.distance_difference = value.as_int().ok_or_else(invalid)?
+            }
+            "final_rank" => self.final_rank = value.as_int().ok_or_else(invalid)?,
+            "time_official" => self.time_official = value.as_bool().ok_or_else(invalid)?,
+            "official_margin" => self.official_margin = value.as_f64().ok_or_else(invalid)?,
+            "fastest_section_time" => {
+                self.fastest_section_time = value.as_time().ok_or_else(invalid)?
+            }
+            "fastest_section_index" => {
+                self.fastest_section_index = value.as_int().ok_or_else(invalid)?
+            }
+            "top_speed" => self.top_speed = value.as_f64().ok_or_else(invalid)?,
+            "top_speed_index" => self.top_speed_index = value.as_int().ok_or_else(invalid)?,
+            "finish_time" => self.finish_time = value.as_time().ok_or_else(invalid)?,
+            "result_state" => self.result_state = value.as_str().ok_or_else(invalid)?.to_string(),
+            "result_substate" => {
+                self.result_substate = value.as_str().ok_or_else(invalid)?.to_string()
+            }
+            _ => return Err(format!("no field {}", name)),
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 
 use super::{fssummary::FastestSectionSummary, horsesummary::HorseSummary};
+use crate::analysis::pace::Pace;
+use crate::fields::{invalid_value, FieldKind, FieldValue, Fields};
 use crate::*;
 use serde::{Deserialize, Serialize};
 
@@ -20,6 +22,18 @@ pub struct RaceSummary {
     pub rail_position: String,
     pub fastest_sections: Vec<FastestSectionSummary>,
     pub horses: Vec<HorseSummary>,
+    // The leader's splits and the pace they make, set by
+    // `analysis::pace::rate_pace` once benchmarks are known
+    #[serde(default)]
+    pub early_split: Option<NaiveTime>,
+    #[serde(default)]
+    pub late_split: Option<NaiveTime>,
+    #[serde(default)]
+    pub pace: Option<Pace>,
+    #[serde(default)]
+    pub early_pace_index: Option<f64>,
+    #[serde(default)]
+    pub late_pace_index: Option<f64>,
 }
 
 impl RaceSummary {
@@ -38,6 +52,11 @@ impl RaceSummary {
             rail_position: String::new(),
             fastest_sections: Vec::new(),
             horses: Vec::new(),
+            early_split: None,
+            late_split: None,
+            pace: None,
+            early_pace_index: None,
+            late_pace_index: None,
         }
     }
 
@@ -60,6 +79,25 @@ impl RaceSummary {
     pub fn add_horse(&mut self, sum: HorseSummary) {
         self.horses.push(sum);
     }
+
+    /// The race's fastest section ending `cumulated_distance` metres from
+    /// the start.
+    pub fn fastest_section(&self, cumulated_distance: i32) -> Option<&FastestSectionSummary> {
+        self.fastest_sections
+            .iter()
+            .find(|section| section.cumulated_distance == cumulated_distance)
+    }
+
+    /// Race distance in metres: the furthest cumulated distance of any
+    /// section, or `None` when no sections were recorded.
+    pub fn distance(&self) -> Option<i32> {
+        let horses = self
+            .horses
+            .iter()
+            .flat_map(|horse| horse.sections.iter().map(|s| s.cumulated_distance));
+        let fastest = self.fastest_sections.iter().map(|s| s.cumulated_distance);
+        horses.chain(fastest).max().filter(|distance| *distance > 0)
+    }
 }
 
 impl ValueProcessor for RaceSummary {
@@ -153,3 +191,76 @@ impl ValueProcessor for RaceSummary {
         }
     }
 }
+
+impl Fields for RaceSummary {
+    const FIELDS: &'static [(&'static str, FieldKind)] = &[
+        ("event_date", FieldKind::Date),
+        ("meeting_code", FieldKind::Int),
+        ("race_number", FieldKind::Int),
+        ("race_code", FieldKind::Int),
+        ("event_name", FieldKind::Text),
+        ("course_name", FieldKind::Text),
+        ("race_name", FieldKind::Text),
+        ("finish_time", FieldKind::Time),
+        ("track_name", FieldKind::Text),
+        ("track_condition", FieldKind::Text),
+        ("rail_position", FieldKind::Text),
+        ("early_split", FieldKind::Time),
+        ("late_split", FieldKind::Time),
+        ("pace", FieldKind::Text),
+        ("early_pace_index", FieldKind::Float),
+        ("late_pace_index", FieldKind::Float),
+    ];
+
+    fn field(&self, name: &str) -> Option<FieldValue> {
+        let value = match name {
+            "event_date" => self.event_date.into(),
+            "meeting_code" => self.meeting_code.into(),
+            "race_number" => self.race_number.into(),
+            "race_code" => self.race_code.into(),
+            "event_name" => self.event_name.as_str().into(),
+            "course_name" => self.course_name.as_str().into(),
+            "race_name" => self.race_name.as_str().into(),
+            "finish_time" => self.finish_time.into(),
+            "track_name" => self.track_name.as_str().into(),
+            "track_condition" => self.track_condition.as_str().into(),
+            "rail_position" => self.rail_position.as_str().into(),
+            "early_split" => self.early_split.into(),
+            "late_split" => self.late_split.into(),
+            "pace" => self.pace.map(|pace| pace.to_string()).as_deref().into(),
+            "early_pace_index" => self.early_pace_index.into(),
+            "late_pace_index" => self.late_pace_index.into(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    fn set_field(&mut self, name: &str, value: &FieldValue) -> Result<(), String> {
+        let invalid = || invalid_value(name, value);
+        match name {
+            "event_date" => self.event_date = value.as_date().ok_or_else(invalid)?,
+            "meeting_code" => self.meeting_code = value.as_int().ok_or_else(invalid)?,
+            "race_number" => self.race_number = value.as_int().ok_or_else(invalid)?,
+            "race_code" => self.race_code = value.as_int().ok_or_else(invalid)?,
+            "event_name" => self.event_name = value.as_str().ok_or_else(invalid)?.to_string(),
+            "course_name" => self.course_name = value.as_str().ok_or_else(invalid)?.to_string(),
+            "race_name" => self.race_name = value.as_str().ok_or_else(invalid)?.to_string(),
+            "finish_time" => self.finish_time = value.as_time().ok_or_else(invalid)?,
+            "track_name" => self.track_name = value.as_str().ok_or_else(invalid)?.to_string(),
+            "track_condition" => {
+                self.track_condition = value.as_str().ok_or_else(invalid)?.to_string()
+            }
+            "rail_position" => self.rail_position = value.as_str().ok_or_else(invalid)?.to_string(),
+            "early_split" => self.early_split = Some(value.as_time().ok_or_else(invalid)?),
+            "late_split" => self.late_split = Some(value.as_time().ok_or_else(invalid)?),
+            "pace" => {
+                let pace = value.as_str().and_then(|pace| pace.parse().ok());
+                self.pace = Some(pace.ok_or_else(invalid)?)
+            }
+            "early_pace_index" => self.early_pace_index = Some(value.as_f64().ok_or_else(invalid)?),
+            "late_pace_index" => self.late_pace_index = Some(value.as_f64().ok_or_else(invalid)?),
+            _ => return Err(format!("no field {}", name)),
+        }
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use super::fssummary::FastestSectionSummary;
+use crate::fields::{invalid_value, FieldKind, FieldValue, Fields};
 use crate::ValueProcessor;
 use crate::*;
 use chrono::NaiveTime;
@@ -34,6 +36,11 @@ impl SectionSummary {
             avg_distance_rail: 0.0,                                  // Initialize to default value
         }
     }
+
+    /// Seconds this section took over the fastest time for it in the race.
+    pub fn deficit(&self, fastest: &FastestSectionSummary) -> f64 {
+        (self.section_time - fastest.section_time).num_milliseconds() as f64 / 1000.0
+    }
 }
 
 impl ValueProcessor for SectionSummary {
@@ -148,3 +155,58 @@ impl ValueProcessor for SectionSummary {
         }
     }
 }
+
+impl Fields for SectionSummary {
+    const FIELDS: &'static [(&'static str, FieldKind)] = &[
+        ("cumulated_distance", FieldKind::Int),
+        ("margin_decimal", FieldKind::Float),
+        ("real_distance", FieldKind::Float),
+        ("rank", FieldKind::Int),
+        ("intermediate_time", FieldKind::Time),
+        ("section_time", FieldKind::Time),
+        ("avg_speed", FieldKind::Float),
+        ("top_speed", FieldKind::Float),
+        ("avg_stride_freq", FieldKind::Float),
+        ("average_stride_length", FieldKind::Float),
+        ("avg_distance_rail", FieldKind::Float),
+    ];
+
+    fn field(&self, name: &str) -> Option<FieldValue> {
+        let value = match name {
+            "cumulated_distance" => self.cumulated_distance.into(),
+            "margin_decimal" => self.margin_decimal.into(),
+            "real_distance" => self.real_distance.into(),
+            "rank" => self.rank.into(),
+            "intermediate_time" => self.intermediate_time.into(),
+            "section_time" => self.section_time.into(),
+            "avg_speed" => self.avg_speed.into(),
+            "top_speed" => self.top_speed.into(),
+            "avg_stride_freq" => self.avg_stride_freq.into(),
+            "average_stride_length" => self.average_stride_length.into(),
+            "avg_distance_rail" => self.avg_distance_rail.into(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    fn set_field(&mut self, name: &str, value: &FieldValue) -> Result<(), String> {
+        let invalid = || invalid_value(name, value);
+        match name {
+            "cumulated_distance" => self.cumulated_distance = value.as_int().ok_or_else(invalid)?,
+            "margin_decimal" => self.margin_decimal = value.as_f64().ok_or_else(invalid)?,
+            "real_distance" => self.real_distance = value.as_f64().ok_or_else(invalid)?,
+            "rank" => self.rank = value.as_int().ok_or_else(invalid)?,
+            "intermediate_time" => self.intermediate_time = value.as_time().ok_or_else(invalid)?,
+            "section_time" => self.section_time = value.as_time().ok_or_else(invalid)?,
+            "avg_speed" => self.avg_speed = value.as_f64().ok_or_else(invalid)?,
+            "top_speed" => self.top_speed = value.as_f64().ok_or_else(invalid)?,
+            "avg_stride_freq" => self.avg_stride_freq = value.as_f64().ok_or_else(invalid)?,
+            "average_stride_length" => {
+                self.average_stride_length = value.as_f64().ok_or_else(invalid)?
+            }
+            "avg_distance_rail" => self.avg_distance_rail = value.as_f64().ok_or_else(invalid)?,
+            _ => return Err(format!("no field {}", name)),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use sectionals::extract::{unzip, Action, ExtractOptions, ZipLimits};
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::Path;
+
+const NAME: &str = "20230818_Eagle Farm_T_R01.xml";
+
+// An archive of one entry that compresses far better than 100 to 1
+fn archive(dir: &Path, size: usize) -> Vec<u8> {
+    let data = b"<Section><Rank>1</Rank></Section>\n".repeat(size / 34);
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file(NAME, zip::write::SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(&data).unwrap();
+    fs::write(dir.join("meeting.zip"), zip.finish().unwrap().into_inner()).unwrap();
+    data
+}
+
+fn options(dir: &Path, limits: ZipLimits) -> ExtractOptions {
+    ExtractOptions {
+        data_dir: dir.join("data"),
+        output_dir: dir.join("out"),
+        limits,
+        ..ExtractOptions::default()
+    }
+}
+
+#[test]
+fn a_small_highly_compressible_entry_is_extracted() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("data")).unwrap();
+    let data = archive(&dir.path().join("data"), 1024 * 1024);
+
+    let log = unzip(&options(dir.path(), ZipLimits::default())).unwrap();
+    assert_eq!(log.count(Action::Extracted), 1);
+    assert_eq!(fs::read(dir.path().join("out").join(NAME)).unwrap(), data);
+}
+
+#[test]
+fn the_ratio_applies_above_its_minimum_size() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("data")).unwrap();
+    archive(&dir.path().join("data"), 1024 * 1024);
+
+    let limits = ZipLimits {
+        ratio_min_size: 64 * 1024,
+        ..ZipLimits::default()
+    };
+    let log = unzip(&options(dir.path(), limits)).unwrap();
+    assert_eq!(log.count(Action::Rejected), 1);
+    assert!(!dir.path().join("out").join(NAME).exists());
+}
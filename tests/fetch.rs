@@ -0,0 +1,80 @@
+use sectionals::fetch::{fetch_sectionals, FetchOptions};
+use std::io::{Cursor, Write};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const LISTING: &str = include_str!("fixtures/sectionals_listing.html");
+
+fn archive() -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file(
+        "20240113_Eagle Farm_T_R01.xml",
+        zip::write::SimpleFileOptions::default(),
+    )
+    .unwrap();
+    zip.write_all(b"<RaceSummary/>").unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+// Serves the saved listing page and its archives, one request per connection
+async fn serve(listener: TcpListener) {
+    let archive = archive();
+    loop {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let n = socket.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+        let request = String::from_utf8_lossy(&request);
+        let path = request.split_whitespace().nth(1).unwrap_or("/");
+        let (status, body) = match path {
+            "/sectionals" => ("200 OK", LISTING.as_bytes().to_vec()),
+            "/files/20240113_Eagle%20Farm.zip" => ("200 OK", archive.clone()),
+            "/files/20240127_Ipswich.zip" => ("200 OK", b"<html>not a zip</html>".to_vec()),
+            _ => ("404 Not Found", Vec::new()),
+        };
+        let head = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            body.len()
+        );
+        socket.write_all(head.as_bytes()).await.unwrap();
+        socket.write_all(&body).await.unwrap();
+        socket.shutdown().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn fetches_new_archives_and_reports_bad_ones() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(serve(listener));
+
+    let data = tempfile::tempdir().unwrap();
+    let options = FetchOptions {
+        listing_url: format!("http://{}/sectionals", address),
+        data_dir: data.path().to_path_buf(),
+    };
+
+    let summary = fetch_sectionals(&options).await.unwrap();
+    let stored = data.path().join("2024/01/20240113_Eagle Farm.zip");
+    assert_eq!(summary.downloaded, vec![stored.clone()]);
+    assert_eq!(std::fs::read(&stored).unwrap(), archive());
+    assert!(summary.skipped.is_empty());
+    let invalid: Vec<&str> = summary.invalid.iter().map(|(url, _)| url.path()).collect();
+    assert_eq!(
+        invalid,
+        ["/files/20240120_Doomben.zip", "/files/20240127_Ipswich.zip"]
+    );
+    assert!(!data.path().join("2024/01/20240120_Doomben.zip").exists());
+
+    // Archives already stored are not downloaded again
+    let summary = fetch_sectionals(&options).await.unwrap();
+    assert!(summary.downloaded.is_empty());
+    assert_eq!(summary.skipped, vec![stored]);
+}
@@ -0,0 +1,131 @@
+use sectionals::export::long::sections_table;
+use sectionals::export::schema::Schema;
+use sectionals::export::table::write_table_csv;
+use sectionals::export::tables::TableOptions;
+use sectionals::export::text::TextOptions;
+use sectionals::export::wide::write_wide_csv;
+use sectionals::fields::Fields;
+use sectionals::import::{CsvImport, CsvLayout};
+use sectionals::parse_file;
+use sectionals::structs::horsesummary::HorseSummary;
+use sectionals::structs::racesummary::RaceSummary;
+use std::path::Path;
+use std::slice;
+
+const FIXTURE: &str = "tests/fixtures/20230818_Eagle_Farm_T_R01.xml";
+
+fn race() -> RaceSummary {
+    parse_file(Path::new(FIXTURE)).unwrap()
+}
+
+fn runner_csv(race: &RaceSummary) -> String {
+    let mut csv = Vec::new();
+    write_wide_csv(
+        slice::from_ref(race),
+        &Schema::default(),
+        &TextOptions::default(),
+        &mut csv,
+    )
+    .unwrap();
+    String::from_utf8(csv).unwrap()
+}
+
+fn sections_csv(race: &RaceSummary) -> String {
+    let mut csv = Vec::new();
+    write_table_csv(
+        &sections_table(slice::from_ref(race), &TableOptions::default()),
+        &TextOptions::default(),
+        &mut csv,
+    )
+    .unwrap();
+    String::from_utf8(csv).unwrap()
+}
+
+// Reads the exports in order into one race
+fn reimport(exports: &[(&str, CsvLayout)]) -> RaceSummary {
+    let mut import = CsvImport::new(Schema::default());
+    for (csv, layout) in exports {
+        let read = import.read(Path::new("export.csv"), csv).unwrap();
+        assert_eq!(read.layout, *layout);
+        assert!(read.skipped.is_empty(), "{:?}", read.skipped);
+    }
+    let mut races = import.finish();
+    assert_eq!(races.len(), 1);
+    races.remove(0).1
+}
+
+fn assert_fields_eq<T: Fields>(imported: &T, original: &T, what: &str) {
+    for (field, _) in T::FIELDS {
+        assert_eq!(
+            imported.field(field),
+            original.field(field),
+            "{} {}",
+            what,
+            field
+        );
+    }
+}
+
+fn assert_runners_eq(imported: &RaceSummary, original: &RaceSummary) {
+    assert_fields_eq(imported, original, "race");
+    assert_eq!(imported.horses.len(), original.horses.len());
+    for (imported, original) in imported.horses.iter().zip(&original.horses) {
+        assert_fields_eq(imported, original, &format!("horse {}", original.code));
+    }
+}
+
+#[test]
+fn runner_csv_round_trips() {
+    let original = race();
+    let imported = reimport(&[(&runner_csv(&original), CsvLayout::Runners)]);
+
+    assert_runners_eq(&imported, &original);
+    let distance = original.distance().unwrap();
+    for (imported, original) in imported.horses.iter().zip(&original.horses) {
+        for metres in [600, 400, 200] {
+            let split = |horse: &HorseSummary| {
+                horse
+                    .section_from_finish(distance, metres)
+                    .map(|section| (section.section_time, section.intermediate_time))
+            };
+            assert_eq!(split(imported), split(original), "L{}", metres);
+        }
+    }
+}
+
+#[test]
+fn sections_csv_round_trips() {
+    let original = race();
+    let imported = reimport(&[
+        (&runner_csv(&original), CsvLayout::Runners),
+        (&sections_csv(&original), CsvLayout::Sections),
+    ]);
+
+    assert_runners_eq(&imported, &original);
+    for (imported, original) in imported.horses.iter().zip(&original.horses) {
+        assert_eq!(imported.sections.len(), original.sections.len());
+        let what = format!("horse {} section", original.code);
+        for (imported, original) in imported.sections.iter().zip(&original.sections) {
+            assert_fields_eq(imported, original, &what);
+        }
+    }
+}
+
+#[test]
+fn bad_rows_are_skipped() {
+    let csv = runner_csv(&race());
+    let mut lines: Vec<String> = csv.lines().map(str::to_string).collect();
+    let bib = lines[0].split(',').position(|h| h == "bib").unwrap();
+    let mut cells: Vec<&str> = lines[2].split(',').collect();
+    cells[bib] = "abc";
+    lines[2] = cells.join(",");
+
+    let mut import = CsvImport::new(Schema::default());
+    let read = import
+        .read(Path::new("export.csv"), &lines.join("\n"))
+        .unwrap();
+    assert_eq!(read.rows, 2);
+    assert_eq!(read.skipped.len(), 1);
+    assert_eq!(read.skipped[0].0, 3);
+    assert_eq!(import.finish()[0].1.horses.len(), 2);
+}